@@ -1,6 +1,6 @@
 use crate::{
     document::{DocumentIoContext, DocumentIoError},
-    io::{FileEditorIo},
+    io::{FileEditorIo, PackageEditorIo},
     project::EditorProject,
     EditorProjectFolder,
 };
@@ -14,6 +14,7 @@ use bevy_egui::{
 };
 use bevy_flycam::PlayerPlugin;
 use bevy_infinite_grid::{InfiniteGridBundle, InfiniteGridMaterial, InfiniteGridPlugin};
+use bevy_quake_map::game_config::FilesystemSettings;
 use bevy_quake_map_editor_common::io::MapIo;
 use futures_lite::future;
 use std::sync::Arc;
@@ -46,8 +47,18 @@ impl FromWorld for EditorContext {
         let root = world.resource::<EditorProjectFolder>().0.clone();
         let task_pool = world.resource::<IoTaskPool>().0.clone();
 
+        // Mount any packages in the project folder, using the default game config's filesystem
+        let io: Arc<dyn MapIo> =
+            match PackageEditorIo::new(FileEditorIo::new(&root), &FilesystemSettings::default()) {
+                Ok(io) => Arc::new(io),
+                Err(err) => {
+                    error!("failed to mount packages in {}: {}", root.display(), err);
+                    Arc::new(FileEditorIo::new(&root))
+                }
+            };
+
         Self {
-            io: Arc::new(FileEditorIo::new(&root)),
+            io,
             project: None,
             task_pool,
             components: Vec::new(),
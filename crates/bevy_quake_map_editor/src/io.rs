@@ -1,3 +1,8 @@
+use bevy::log::warn;
+use bevy_quake_map::{
+    game_config::FilesystemSettings,
+    vfs::{Archive, Vfs},
+};
 use bevy_quake_map_editor_common::io::{MapIo, MapIoError, MapIoRead, MapIoWrite};
 use std::{
     fs, io,
@@ -55,3 +60,189 @@ impl MapIoWrite for FileEditorIo {
 }
 
 impl MapIo for FileEditorIo {}
+
+/// Reads files from `inner`, falling back to the packages mounted in `vfs`
+pub struct PackageEditorIo<T: MapIoRead> {
+    inner: T,
+    vfs: Vfs,
+}
+
+impl<T: MapIoRead> PackageEditorIo<T> {
+    /// Mounts the packages in `filesystem.search_path`, as read through `inner`.
+    /// Packages which can't be read are skipped, like in `FileAssetProvider`.
+    pub fn new(inner: T, filesystem: &FilesystemSettings) -> Result<Self, MapIoError> {
+        let mut vfs = Vfs::default();
+
+        let files = match inner.read_directory(Path::new(&filesystem.search_path)) {
+            Ok(files) => files,
+            Err(MapIoError::NotFound(..)) => return Ok(Self { inner, vfs }),
+            Err(err) => return Err(err),
+        };
+
+        for (path, format) in Vfs::package_paths(filesystem, files) {
+            let archive = match inner.read_file(&path) {
+                Ok(bytes) => Archive::parse(format, bytes),
+                Err(err) => {
+                    warn!("failed to read {}: {}", path.display(), err);
+                    continue;
+                }
+            };
+
+            match archive {
+                Ok(archive) => vfs.mount(archive),
+                Err(err) => warn!("failed to mount {}: {}", path.display(), err),
+            }
+        }
+
+        Ok(Self { inner, vfs })
+    }
+}
+
+impl<T: MapIoRead> MapIoRead for PackageEditorIo<T> {
+    fn read_file(&self, path: &Path) -> Result<Vec<u8>, MapIoError> {
+        match self.inner.read_file(path) {
+            Err(MapIoError::NotFound(..)) => match self.vfs.read_file(path) {
+                Some(contents) => {
+                    Ok(contents.map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?)
+                }
+                None => Err(MapIoError::NotFound(path.to_path_buf())),
+            },
+            result => result,
+        }
+    }
+
+    fn read_directory(&self, path: &Path) -> Result<Box<dyn Iterator<Item = PathBuf>>, MapIoError> {
+        let packaged = self.vfs.read_directory(path);
+
+        match self.inner.read_directory(path) {
+            // Loose files take priority, so packaged files are only listed if they aren't also loose.
+            // Both are in `path`, but loose paths may include the root, so only names are compared.
+            Ok(files) => {
+                let files = files.collect::<Vec<_>>();
+                let packaged = packaged
+                    .into_iter()
+                    .filter(|file| !files.iter().any(|f| f.file_name() == file.file_name()))
+                    .collect::<Vec<_>>();
+
+                Ok(Box::new(files.into_iter().chain(packaged)))
+            }
+            Err(MapIoError::NotFound(..)) if !packaged.is_empty() => {
+                Ok(Box::new(packaged.into_iter()))
+            }
+            Err(err) => Err(err),
+        }
+    }
+}
+
+/// Packages are read-only, so writes only go to `inner`
+impl<T: MapIo> MapIoWrite for PackageEditorIo<T> {
+    fn write_file(&self, path: &Path, contents: &[u8]) -> Result<(), MapIoError> {
+        self.inner.write_file(path, contents)
+    }
+
+    fn delete_file(&self, path: &Path) -> Result<(), MapIoError> {
+        self.inner.delete_file(path)
+    }
+
+    fn move_file(&self, from: &Path, to: &Path) -> Result<(), MapIoError> {
+        self.inner.move_file(from, to)
+    }
+
+    fn create_directory(&self, path: &Path) -> Result<(), MapIoError> {
+        self.inner.create_directory(path)
+    }
+}
+
+impl<T: MapIo> MapIo for PackageEditorIo<T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::PackageEditorIo;
+    use bevy_quake_map::{game_config::FilesystemSettings, vfs::test_support::build_pak};
+    use bevy_quake_map_editor_common::io::{MapIoError, MapIoRead};
+    use std::{
+        collections::HashMap,
+        path::{Path, PathBuf},
+    };
+
+    /// Files in memory, with directories implied by their paths
+    struct MemoryIo(HashMap<PathBuf, Vec<u8>>);
+
+    impl MapIoRead for MemoryIo {
+        fn read_file(&self, path: &Path) -> Result<Vec<u8>, MapIoError> {
+            self.0
+                .get(path)
+                .cloned()
+                .ok_or_else(|| MapIoError::NotFound(path.to_path_buf()))
+        }
+
+        fn read_directory(
+            &self,
+            path: &Path,
+        ) -> Result<Box<dyn Iterator<Item = PathBuf>>, MapIoError> {
+            let files = self
+                .0
+                .keys()
+                .filter(|file| file.parent() == Some(path))
+                .cloned()
+                .collect::<Vec<_>>();
+
+            if files.is_empty() {
+                return Err(MapIoError::NotFound(path.to_path_buf()));
+            }
+
+            Ok(Box::new(files.into_iter()))
+        }
+    }
+
+    #[test]
+    fn test_package_io() {
+        let files = HashMap::from([
+            (
+                PathBuf::from("./pak0.pak"),
+                build_pak(&[
+                    ("textures/wall.png", b"old wall"),
+                    ("textures/floor.png", b"floor"),
+                    ("gfx/palette.lmp", b"palette"),
+                ]),
+            ),
+            (PathBuf::from("./pak1.pak"), b"not a pak".to_vec()),
+            (PathBuf::from("textures/wall.png"), b"new wall".to_vec()),
+        ]);
+
+        let io = PackageEditorIo::new(MemoryIo(files), &FilesystemSettings::default())
+            .expect("failed to mount packages");
+
+        // Loose files take priority, and the invalid package is skipped
+        assert_eq!(
+            io.read_file(Path::new("textures/wall.png")).unwrap(),
+            b"new wall"
+        );
+        assert_eq!(
+            io.read_file(Path::new("gfx/palette.lmp")).unwrap(),
+            b"palette"
+        );
+        assert!(matches!(
+            io.read_file(Path::new("gfx/colormap.lmp")),
+            Err(MapIoError::NotFound(..))
+        ));
+
+        // Directories which only exist in packages are listed from them
+        assert_eq!(
+            io.read_directory(Path::new("gfx"))
+                .unwrap()
+                .collect::<Vec<_>>(),
+            vec![PathBuf::from("gfx/palette.lmp")]
+        );
+        // Files which are both loose and packaged are listed once
+        assert_eq!(
+            io.read_directory(Path::new("textures"))
+                .unwrap()
+                .collect::<Vec<_>>(),
+            vec![
+                PathBuf::from("textures/wall.png"),
+                PathBuf::from("textures/floor.png")
+            ]
+        );
+    }
+}
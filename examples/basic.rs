@@ -1,56 +1,8 @@
-use anyhow::Result as AResult;
-use bevy::{
-    asset::{AssetLoader, BoxedFuture},
-    prelude::*,
-    render::texture::CompressedImageFormats,
-};
+use bevy::prelude::*;
 use bevy_flycam::PlayerPlugin;
 use bevy_inspector_egui::WorldInspectorPlugin;
-use bevy_quake_map::{
-    get_supported_compressed_formats, load_map, FileAssetProvider, MapAssetProvider, MapPlugin,
-};
+use bevy_quake_map::MapPlugin;
 use bevy_rapier3d::prelude::*;
-use std::sync::Arc;
-
-struct MapLoader {
-    asset_provider: Arc<dyn MapAssetProvider>,
-    supported_compressed_formats: CompressedImageFormats,
-}
-
-impl FromWorld for MapLoader {
-    fn from_world(world: &mut World) -> Self {
-        Self {
-            asset_provider: Arc::new(FileAssetProvider::from_world(world)),
-            supported_compressed_formats: get_supported_compressed_formats(world),
-        }
-    }
-}
-
-impl AssetLoader for MapLoader {
-    fn load<'a>(
-        &'a self,
-        bytes: &'a [u8],
-        load_context: &'a mut bevy::asset::LoadContext,
-    ) -> BoxedFuture<'a, AResult<()>> {
-        Box::pin(async move {
-            let map = load_map(
-                bytes,
-                load_context,
-                self.supported_compressed_formats,
-                self.asset_provider.clone(),
-            )
-            .await?;
-
-            load_context.set_default_asset(map);
-
-            Ok(())
-        })
-    }
-
-    fn extensions(&self) -> &[&str] {
-        &["map"]
-    }
-}
 
 fn main() {
     App::new()
@@ -59,7 +11,6 @@ fn main() {
         .add_plugin(WorldInspectorPlugin::new())
         .add_plugin(PlayerPlugin)
         .add_plugin(MapPlugin)
-        .init_asset_loader::<MapLoader>()
         .add_startup_system(setup)
         .run();
 }
@@ -0,0 +1,30 @@
+//! Helpers for reading little-endian binary formats
+
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+#[error("unexpected end of data")]
+pub struct UnexpectedEof;
+
+pub fn read_bytes(data: &[u8], offset: usize, len: usize) -> Result<&[u8], UnexpectedEof> {
+    data.get(offset..offset.checked_add(len).ok_or(UnexpectedEof)?)
+        .ok_or(UnexpectedEof)
+}
+
+pub fn read_u16(data: &[u8], offset: usize) -> Result<u16, UnexpectedEof> {
+    let bytes = read_bytes(data, offset, 2)?;
+    Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+pub fn read_u32(data: &[u8], offset: usize) -> Result<u32, UnexpectedEof> {
+    let bytes = read_bytes(data, offset, 4)?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// Reads a fixed-size, null-padded name
+pub fn read_name(data: &[u8], offset: usize, len: usize) -> Result<String, UnexpectedEof> {
+    let bytes = read_bytes(data, offset, len)?;
+    let end = bytes.iter().position(|b| *b == 0).unwrap_or(len);
+
+    Ok(String::from_utf8_lossy(&bytes[..end]).into_owned())
+}
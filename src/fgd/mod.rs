@@ -10,6 +10,9 @@ const TAB: &str = "    ";
 mod to_fgd_literal;
 pub use to_fgd_literal::*;
 
+mod parsing;
+pub use parsing::parse_fgd;
+
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub struct FgdFile {
     pub name: String,
@@ -0,0 +1,595 @@
+//! A parser for FGD files, accepting everything `FgdFile::serialize` emits
+//! as well as common constructs from real-world FGDs (quoted includes, `+` concatenation, comments).
+//! Anything which cannot be represented by `FgdFile` (e.g. `@mapsize`, unknown class properties) is skipped.
+
+use super::{
+    Choice, EntityProperty, EntityPropertyData, FgdClass, FgdClassProperty, FgdClassType, FgdFile,
+    Flag, FlagsData,
+};
+use crate::parsing::util::{comment, identifier, ignored};
+use glam::{UVec3, Vec3};
+use nom::{
+    branch::alt,
+    bytes::complete::{escaped, is_not, tag, tag_no_case, take_while1},
+    character::complete::{anychar, char, multispace0, multispace1, u32 as uint},
+    combinator::{map, opt, value},
+    error::{context, ContextError, ParseError},
+    multi::{many0, separated_list0, separated_list1},
+    number::complete::float,
+    sequence::{delimited, pair, preceded, separated_pair, terminated, tuple},
+    IResult,
+};
+
+enum FgdItem {
+    Include(String),
+    Class(FgdClass),
+    Skipped,
+}
+
+fn word<'a, E: ParseError<&'a str>>(i: &'a str) -> IResult<&'a str, &'a str, E> {
+    take_while1(|c: char| c.is_alphanumeric() || c == '_')(i)
+}
+
+/// Matches a quoted string, translating `\n` and `\"`
+fn string_literal<'a, E: ParseError<&'a str>>(i: &'a str) -> IResult<&'a str, String, E> {
+    map(
+        delimited(
+            char('"'),
+            opt(escaped(is_not("\\\""), '\\', anychar)),
+            char('"'),
+        ),
+        |s: Option<&str>| s.unwrap_or("").replace("\\n", "\n").replace("\\\"", "\""),
+    )(i)
+}
+
+/// Matches one or more quoted strings joined by `+`
+fn concatenated_string<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    i: &'a str,
+) -> IResult<&'a str, String, E> {
+    context(
+        "string",
+        map(
+            separated_list1(tuple((ignored, char('+'), ignored)), string_literal),
+            |parts| parts.concat(),
+        ),
+    )(i)
+}
+
+/// Matches a (possibly nested) parenthesized, bracketed or braced block and discards it
+fn skipped_block<'a, E: ParseError<&'a str>>(i: &'a str) -> IResult<&'a str, (), E> {
+    alt((
+        delimited(char('('), skipped_contents, char(')')),
+        delimited(char('['), skipped_contents, char(']')),
+        delimited(char('{'), skipped_contents, char('}')),
+    ))(i)
+}
+
+fn skipped_contents<'a, E: ParseError<&'a str>>(i: &'a str) -> IResult<&'a str, (), E> {
+    value(
+        (),
+        many0(alt((
+            value((), string_literal),
+            skipped_block,
+            comment,
+            value((), is_not("()[]{}\"/")),
+            value((), char('/')),
+        ))),
+    )(i)
+}
+
+/// Matches the value of a property field, which may or may not be quoted
+fn raw_value<'a, E: ParseError<&'a str>>(i: &'a str) -> IResult<&'a str, String, E> {
+    alt((
+        string_literal,
+        map(
+            take_while1(|c: char| !c.is_whitespace() && !":=[]".contains(c)),
+            |s: &str| s.to_string(),
+        ),
+    ))(i)
+}
+
+fn class_type<'a, E: ParseError<&'a str>>(i: &'a str) -> IResult<&'a str, FgdClassType, E> {
+    alt((
+        map(tag_no_case("@BaseClass"), |_| FgdClassType::Base),
+        map(tag_no_case("@PointClass"), |_| FgdClassType::Point),
+        map(tag_no_case("@SolidClass"), |_| FgdClassType::Solid),
+    ))(i)
+}
+
+fn arguments<'a, O, E: ParseError<&'a str>>(
+    name: &'static str,
+    parser: impl FnMut(&'a str) -> IResult<&'a str, O, E>,
+) -> impl FnMut(&'a str) -> IResult<&'a str, O, E> {
+    preceded(
+        pair(tag_no_case(name), multispace0),
+        delimited(
+            pair(char('('), multispace0),
+            parser,
+            pair(multispace0, char(')')),
+        ),
+    )
+}
+
+fn vec3<'a, E: ParseError<&'a str>>(i: &'a str) -> IResult<&'a str, Vec3, E> {
+    map(
+        tuple((
+            terminated(float, multispace1),
+            terminated(float, multispace1),
+            float,
+        )),
+        |(x, y, z)| Vec3::new(x, y, z),
+    )(i)
+}
+
+/// Matches a class property, e.g. `base(A, B)`. Unsupported properties produce `None`.
+fn class_property<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    i: &'a str,
+) -> IResult<&'a str, Option<FgdClassProperty>, E> {
+    context(
+        "class_property",
+        alt((
+            map(
+                arguments(
+                    "base",
+                    separated_list0(tuple((multispace0, char(','), multispace0)), word),
+                ),
+                |bases| {
+                    Some(FgdClassProperty::Base(
+                        bases.into_iter().map(|b| b.to_string()).collect(),
+                    ))
+                },
+            ),
+            map(arguments("model", string_literal), |path| {
+                Some(FgdClassProperty::Model(path))
+            }),
+            map(
+                arguments(
+                    "color",
+                    tuple((
+                        terminated(uint, multispace1),
+                        terminated(uint, multispace1),
+                        uint,
+                    )),
+                ),
+                |(r, g, b)| Some(FgdClassProperty::Color(UVec3::new(r, g, b))),
+            ),
+            map(
+                arguments(
+                    "size",
+                    separated_pair(vec3, tuple((multispace0, char(','), multispace0)), vec3),
+                ),
+                |(p1, p2)| Some(FgdClassProperty::Size(p1, p2)),
+            ),
+            map(
+                pair(word, opt(preceded(multispace0, skipped_block))),
+                |_| None,
+            ),
+        )),
+    )(i)
+}
+
+fn choice<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    i: &'a str,
+) -> IResult<&'a str, (String, String), E> {
+    context(
+        "choice",
+        separated_pair(
+            raw_value,
+            tuple((ignored, char(':'), ignored)),
+            concatenated_string,
+        ),
+    )(i)
+}
+
+fn flag<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    i: &'a str,
+) -> IResult<&'a str, Option<Flag>, E> {
+    context(
+        "flag",
+        map(
+            tuple((
+                raw_value,
+                preceded(tuple((ignored, char(':'), ignored)), concatenated_string),
+                opt(preceded(tuple((ignored, char(':'), ignored)), raw_value)),
+            )),
+            |(flag, name, default)| {
+                Some(Flag {
+                    flag: flag.parse().ok()?,
+                    name,
+                    default: default.map(|d| parse_bool(&d)).unwrap_or_default(),
+                })
+            },
+        ),
+    )(i)
+}
+
+fn list<'a, O, E: ParseError<&'a str>>(
+    item: impl FnMut(&'a str) -> IResult<&'a str, O, E>,
+) -> impl FnMut(&'a str) -> IResult<&'a str, Vec<O>, E> {
+    preceded(
+        tuple((ignored, char('='), ignored)),
+        delimited(
+            pair(char('['), ignored),
+            many0(terminated(item, ignored)),
+            char(']'),
+        ),
+    )
+}
+
+/// Matches an optional `: value` field, which may be present but empty
+fn field<'a, O, E: ParseError<&'a str>>(
+    parser: impl FnMut(&'a str) -> IResult<&'a str, O, E>,
+) -> impl FnMut(&'a str) -> IResult<&'a str, Option<O>, E> {
+    map(
+        opt(preceded(tuple((ignored, char(':'), ignored)), opt(parser))),
+        Option::flatten,
+    )
+}
+
+fn parse_bool(value: &str) -> bool {
+    matches!(value.trim(), "1" | "true" | "yes")
+}
+
+fn property_data<T: std::str::FromStr + super::ToFgdLiteral + Default>(
+    name: &str,
+    display_name: Option<String>,
+    default: Option<String>,
+    description: Option<String>,
+) -> EntityPropertyData<T> {
+    EntityPropertyData {
+        name: name.to_string(),
+        display_name: display_name.unwrap_or_default(),
+        default: default.and_then(|d| d.parse().ok()).unwrap_or_default(),
+        description: description.unwrap_or_default(),
+    }
+}
+
+/// Matches an entity property, e.g. `name(type) : "Display name" : default : "Description"`
+fn entity_property<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    i: &'a str,
+) -> IResult<&'a str, EntityProperty, E> {
+    let (i, (name, type_name)) = context(
+        "entity_property",
+        pair(
+            word,
+            delimited(
+                tuple((multispace0, char('('), multispace0)),
+                word,
+                tuple((
+                    multispace0,
+                    char(')'),
+                    opt(preceded(multispace1, tag("readonly"))),
+                )),
+            ),
+        ),
+    )(i)?;
+
+    let (i, display_name) = field(concatenated_string)(i)?;
+
+    if type_name.eq_ignore_ascii_case("flags") {
+        let (i, flags) = list(flag)(i)?;
+
+        return Ok((
+            i,
+            EntityProperty::Flags(FlagsData {
+                name: name.to_string(),
+                flags: flags.into_iter().flatten().collect(),
+            }),
+        ));
+    }
+
+    let (i, default) = field(raw_value)(i)?;
+    let (i, description) = field(concatenated_string)(i)?;
+
+    let property = match type_name.to_lowercase().as_str() {
+        "integer" => {
+            EntityProperty::Integer(property_data(name, display_name, default, description))
+        }
+        "float" => EntityProperty::Float(property_data(name, display_name, default, description)),
+        "boolean" => EntityProperty::Boolean(EntityPropertyData {
+            name: name.to_string(),
+            display_name: display_name.unwrap_or_default(),
+            default: default.map(|d| parse_bool(&d)).unwrap_or_default(),
+            description: description.unwrap_or_default(),
+        }),
+        "choices" => {
+            let (i, choices) = list(choice)(i)?;
+
+            // Choices with non-integer keys can't be represented, so fall back to a string
+            let choices = choices
+                .into_iter()
+                .map(|(index, name)| {
+                    Some(Choice {
+                        index: index.parse().ok()?,
+                        name,
+                    })
+                })
+                .collect::<Option<Vec<_>>>();
+
+            let property = match choices {
+                Some(choices) => EntityProperty::Choices(
+                    property_data(name, display_name, default, description),
+                    choices,
+                ),
+                None => {
+                    EntityProperty::String(property_data(name, display_name, default, description))
+                }
+            };
+
+            return Ok((i, property));
+        }
+        _ => EntityProperty::String(property_data(name, display_name, default, description)),
+    };
+
+    Ok((i, property))
+}
+
+fn class<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    i: &'a str,
+) -> IResult<&'a str, FgdClass, E> {
+    context(
+        "class",
+        map(
+            tuple((
+                terminated(class_type, ignored),
+                many0(terminated(class_property, ignored)),
+                preceded(
+                    pair(char('='), ignored),
+                    take_while1(|c: char| !c.is_whitespace() && c != ':' && c != '['),
+                ),
+                opt(preceded(
+                    tuple((ignored, char(':'), ignored)),
+                    concatenated_string,
+                )),
+                delimited(
+                    tuple((ignored, char('['), ignored)),
+                    many0(terminated(entity_property, ignored)),
+                    char(']'),
+                ),
+            )),
+            |(class_type, class_properties, name, description, entity_properties)| FgdClass {
+                class_type,
+                name: name.to_string(),
+                description: description.unwrap_or_default(),
+                class_properties: class_properties.into_iter().flatten().collect(),
+                entity_properties,
+            },
+        ),
+    )(i)
+}
+
+fn include<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    i: &'a str,
+) -> IResult<&'a str, String, E> {
+    context(
+        "include",
+        preceded(
+            pair(tag_no_case("@include"), multispace1),
+            alt((string_literal, map(identifier, |s: &str| s.to_string()))),
+        ),
+    )(i)
+}
+
+/// Matches any other directive (e.g. `@mapsize(-4096, 4096)`) and discards it
+fn skipped_directive<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    i: &'a str,
+) -> IResult<&'a str, (), E> {
+    context(
+        "directive",
+        value(
+            (),
+            tuple((
+                preceded(char('@'), word),
+                opt(preceded(ignored, skipped_block)),
+                opt(preceded(
+                    tuple((ignored, char('='), ignored)),
+                    alt((value((), concatenated_string), value((), word))),
+                )),
+                opt(preceded(ignored, skipped_block)),
+            )),
+        ),
+    )(i)
+}
+
+/// Parses an FGD file. The name is not part of the file, so it is left empty.
+pub fn parse_fgd<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    i: &'a str,
+) -> IResult<&'a str, FgdFile, E> {
+    context(
+        "fgd",
+        map(
+            delimited(
+                ignored,
+                many0(terminated(
+                    alt((
+                        map(include, FgdItem::Include),
+                        map(class, FgdItem::Class),
+                        map(skipped_directive, |_| FgdItem::Skipped),
+                    )),
+                    ignored,
+                )),
+                ignored,
+            ),
+            |items| {
+                let mut fgd = FgdFile {
+                    name: String::new(),
+                    includes: Vec::new(),
+                    classes: Vec::new(),
+                };
+
+                for item in items {
+                    match item {
+                        FgdItem::Include(include) => fgd.includes.push(include),
+                        FgdItem::Class(class) => fgd.classes.push(class),
+                        FgdItem::Skipped => (),
+                    }
+                }
+
+                fgd
+            },
+        ),
+    )(i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{class_property, entity_property, parse_fgd};
+    use crate::fgd::{
+        Choice, EntityProperty, EntityPropertyData, FgdClassProperty, FgdClassType, FlagsData,
+    };
+    use glam::Vec3;
+
+    const TEST_OUTPUT: &str = include_str!("test_data/test_output.fgd");
+
+    #[test]
+    fn test_fgd_round_trip() {
+        let (rest, fgd) =
+            parse_fgd::<nom::error::VerboseError<&str>>(TEST_OUTPUT).expect("failed to parse");
+
+        assert_eq!(rest, "");
+        assert_eq!(fgd.includes, vec!["base1", "base2"]);
+        assert_eq!(
+            fgd.classes[0].description,
+            "Test base class\nMultiline description"
+        );
+        assert_eq!(fgd.serialize(), TEST_OUTPUT);
+    }
+
+    #[test]
+    fn test_parse_class_property() {
+        assert_eq!(
+            class_property::<()>("base(A,B, C )"),
+            Ok((
+                "",
+                Some(FgdClassProperty::Base(vec![
+                    "A".to_string(),
+                    "B".to_string(),
+                    "C".to_string()
+                ]))
+            ))
+        );
+
+        assert_eq!(
+            class_property::<()>("size(-8 -8 0, 8 8 32.5)"),
+            Ok((
+                "",
+                Some(FgdClassProperty::Size(
+                    Vec3::new(-8.0, -8.0, 0.0),
+                    Vec3::new(8.0, 8.0, 32.5)
+                ))
+            ))
+        );
+
+        assert_eq!(
+            class_property::<()>("model({ \"path\": \"progs/player.mdl\" }) asdf"),
+            Ok((" asdf", None))
+        );
+    }
+
+    #[test]
+    fn test_parse_entity_property() {
+        assert_eq!(
+            entity_property::<()>("target(target_destination) : \"Target\" : : \"A\" + \n \"B\""),
+            Ok((
+                "",
+                EntityProperty::String(EntityPropertyData::<String> {
+                    name: "target".to_string(),
+                    display_name: "Target".to_string(),
+                    default: "".to_string(),
+                    description: "AB".to_string(),
+                })
+            ))
+        );
+
+        assert_eq!(
+            entity_property::<()>(
+                "style(Choices) : \"Style\" : \"1\" =\n[\n    0 : \"Normal\" // comment\n    \"1\" : \"Flicker\"\n]"
+            ),
+            Ok((
+                "",
+                EntityProperty::Choices(
+                    EntityPropertyData::<i32> {
+                        name: "style".to_string(),
+                        display_name: "Style".to_string(),
+                        default: 1,
+                        description: "".to_string(),
+                    },
+                    vec![
+                        Choice {
+                            index: 0,
+                            name: "Normal".to_string(),
+                        },
+                        Choice {
+                            index: 1,
+                            name: "Flicker".to_string(),
+                        },
+                    ]
+                )
+            ))
+        );
+
+        assert_eq!(
+            entity_property::<()>("spawnflags(Flags) = [ ]"),
+            Ok((
+                "",
+                EntityProperty::Flags(FlagsData {
+                    name: "spawnflags".to_string(),
+                    flags: Vec::new(),
+                })
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_real_world_fgd() {
+        let (rest, fgd) = parse_fgd::<nom::error::VerboseError<&str>>(
+            r#"
+// Some comment
+//
+@include "base.fgd"
+@mapsize(-4096, 4096)
+
+@SolidClass = worldspawn : "World entity"
+[
+    message(string) : "Text on entering the world"
+    _tb_textures(string) readonly : "Texture collections"
+]
+
+@PointClass size(-16 -16 -24, 16 16 32) color(0 255 0) iconsprite("sprites/player.spr") model({ "path": ":progs/player.mdl" }) = info_player_start : "Player " +
+    "start" []
+
+@BaseClass = Appearflags [
+    spawnflags(Flags) =
+    [
+        256 : "Not on Easy" : 0
+        512 : "Not on Normal"
+    ]
+]
+"#,
+        )
+        .expect("failed to parse");
+
+        assert_eq!(rest, "");
+        assert_eq!(fgd.includes, vec!["base.fgd"]);
+        assert_eq!(fgd.classes.len(), 3);
+
+        let worldspawn = &fgd.classes[0];
+        assert_eq!(worldspawn.class_type, FgdClassType::Solid);
+        assert_eq!(worldspawn.entity_properties.len(), 2);
+        assert_eq!(worldspawn.entity_properties[1].name(), "_tb_textures");
+
+        let player_start = &fgd.classes[1];
+        assert_eq!(player_start.name, "info_player_start");
+        assert_eq!(player_start.description, "Player start");
+        assert_eq!(player_start.class_properties.len(), 2);
+
+        match &fgd.classes[2].entity_properties[0] {
+            EntityProperty::Flags(data) => {
+                assert_eq!(data.flags.len(), 2);
+                assert_eq!(data.flags[1].flag, 512);
+                assert!(!data.flags[1].default);
+            }
+            _ => panic!("expected flags"),
+        }
+    }
+}
@@ -8,6 +8,46 @@ pub struct FaceAttributes {
     pub content_flags: Vec<FaceFlag>,
 }
 
+impl FaceAttributes {
+    /// Names of the surface flags set in `flags` (e.g. from `SurfaceAttributes::surface_flags`)
+    pub fn surface_flag_names(&self, flags: i32) -> Vec<&str> {
+        flag_names(&self.surface_flags, flags)
+    }
+
+    /// Names of the content flags set in `flags` (e.g. from `SurfaceAttributes::content_flags`)
+    pub fn content_flag_names(&self, flags: i32) -> Vec<&str> {
+        flag_names(&self.content_flags, flags)
+    }
+
+    /// The bit for the surface flag called `name`
+    pub fn surface_flag(&self, name: &str) -> Option<i32> {
+        flag_bit(&self.surface_flags, name)
+    }
+
+    /// The bit for the content flag called `name`
+    pub fn content_flag(&self, name: &str) -> Option<i32> {
+        flag_bit(&self.content_flags, name)
+    }
+}
+
+/// Each flag's bit is determined by its index in the list
+fn flag_names(definitions: &[FaceFlag], flags: i32) -> Vec<&str> {
+    definitions
+        .iter()
+        .enumerate()
+        .filter(|(idx, _)| *idx < 32 && flags & (1 << idx) != 0)
+        .filter_map(|(_, flag)| flag.name())
+        .collect()
+}
+
+fn flag_bit(definitions: &[FaceFlag], name: &str) -> Option<i32> {
+    definitions
+        .iter()
+        .take(32)
+        .position(|flag| flag.name() == Some(name))
+        .map(|idx| 1 << idx)
+}
+
 #[derive(Deserialize, Serialize, Debug)]
 #[serde(untagged)]
 pub enum FaceFlag {
@@ -23,6 +63,15 @@ pub enum FaceFlag {
     },
 }
 
+impl FaceFlag {
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            FaceFlag::Unused { .. } => None,
+            FaceFlag::Used { name, .. } => Some(name),
+        }
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug)]
 pub struct FaceAttributeDefaults {
     pub offset: Option<[f32; 2]>,
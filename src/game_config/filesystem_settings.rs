@@ -22,7 +22,7 @@ impl Default for FilesystemSettings {
     }
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
 pub enum PackageFormat {
     IdPak,
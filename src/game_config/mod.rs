@@ -70,6 +70,27 @@ pub enum PackageFormatSettings<T> {
     Extensions { extensions: Vec<String>, format: T },
 }
 
+impl<T> PackageFormatSettings<T> {
+    pub fn format(&self) -> &T {
+        match self {
+            PackageFormatSettings::Extension { format, .. } => format,
+            PackageFormatSettings::Extensions { format, .. } => format,
+        }
+    }
+
+    /// Whether files with this extension (without the leading `.`) use the format
+    pub fn matches_extension(&self, extension: &str) -> bool {
+        match self {
+            PackageFormatSettings::Extension { extension: ext, .. } => {
+                ext.trim_start_matches('.').eq_ignore_ascii_case(extension)
+            }
+            PackageFormatSettings::Extensions { extensions, .. } => extensions
+                .iter()
+                .any(|ext| ext.trim_start_matches('.').eq_ignore_ascii_case(extension)),
+        }
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug)]
 pub struct MapFormatSettings {
     pub format: MapFormat,
@@ -102,7 +123,7 @@ pub struct CompilationTool {
 
 #[cfg(test)]
 mod tests {
-    use crate::game_config::GameConfig;
+    use crate::game_config::{FaceAttributes, GameConfig};
 
     #[test]
     fn test_deserialize() {
@@ -181,4 +202,34 @@ mod tests {
 }
         "#).expect("Deserialization failed");
     }
+
+    #[test]
+    fn test_face_flags() {
+        let face_attribs = serde_json::from_str::<FaceAttributes>(
+            r#"
+{
+    "surfaceflags": [
+        { "name": "light", "description": "Emit light from the surface" },
+        { "name": "slick" },
+        { "unused": true },
+        { "name": "warp" }
+    ],
+    "contentflags": [
+        { "name": "solid" },
+        { "name": "window" }
+    ]
+}
+        "#,
+        )
+        .expect("Deserialization failed");
+
+        assert_eq!(
+            face_attribs.surface_flag_names(0b1101),
+            vec!["light", "warp"]
+        );
+        assert_eq!(face_attribs.content_flag_names(0b10), vec!["window"]);
+        assert_eq!(face_attribs.surface_flag("warp"), Some(0b1000));
+        assert_eq!(face_attribs.content_flag("solid"), Some(1));
+        assert_eq!(face_attribs.content_flag("lava"), None);
+    }
 }
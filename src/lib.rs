@@ -11,7 +11,10 @@ pub mod fgd;
 pub mod game_config;
 pub mod map_data;
 pub mod parsing;
+pub mod texture_formats;
+pub mod vfs;
 
+mod binary;
 mod loader;
 pub use loader::*;
 
@@ -1,5 +1,8 @@
-use super::utils::{map_to_bevy_space3, map_to_bevy_space4};
-use crate::map_data::BrushFace;
+use super::{
+    utils::{map_is_mirrored, map_to_bevy_position, map_to_bevy_space3, map_to_bevy_space4},
+    LightmapChart, LightmapSettings, MapLoaderSettings, MeshLightmap,
+};
+use crate::map_data::{BrushFace, Patch};
 use bevy::{
     prelude::Mesh,
     render::mesh::{Indices, PrimitiveTopology},
@@ -16,20 +19,29 @@ pub struct BrushMeshInfo {
     normals: Vec<Vec3>,
     uvs: Vec<Vec2>,
     tangents: Vec<Vec4>,
+    /// Coordinates of each vertex on its face's plane, for lightmap UVs
+    lightmap_coords: Vec<Vec2>,
+    charts: Vec<LightmapChart>,
 }
 
 impl BrushMeshInfo {
-    /// Pushes a set of vertices, which should be defined by a face and already wound
-    pub fn push_vertices(&mut self, brush_face: &BrushFace, vertices: &[Vec3]) {
+    /// Pushes a set of vertices, which should be defined by a face and already wound,
+    /// and their normals
+    pub fn push_vertices(&mut self, brush_face: &BrushFace, vertices: &[Vec3], normals: &[Vec3]) {
         let u = &brush_face.u;
         let v = &brush_face.v;
 
         // Index of the first vertex in this set
         let begin_idx = self.vertices.len();
 
-        for vertex in vertices {
+        // Any basis of the face's plane will do, as lightmap charts are packed by their bounds
+        let normal = brush_face.normal.normalize().as_vec3();
+        let lightmap_u = normal.any_orthonormal_vector();
+        let lightmap_v = normal.cross(lightmap_u);
+
+        for (vertex, normal) in vertices.iter().zip(normals) {
             self.vertices.push(*vertex);
-            self.normals.push(brush_face.normal.as_vec3());
+            self.normals.push(*normal);
             self.tangents.push(brush_face.tangent().as_vec4());
 
             // UV calculations can take place without swizzles or transformations
@@ -45,11 +57,19 @@ impl BrushMeshInfo {
             v_coord += v.offset as f32;
 
             self.uvs.push(Vec2::new(u_coord, v_coord));
+
+            self.lightmap_coords
+                .push(Vec2::new(lightmap_u.dot(*vertex), lightmap_v.dot(*vertex)));
         }
 
         // Last vertex in this set
         let end_idx = self.vertices.len() - 1;
 
+        self.charts.push(LightmapChart {
+            start: begin_idx,
+            end: end_idx + 1,
+        });
+
         // Perform fan triangulation on this set of vertices
         for i in (begin_idx + 1)..=(end_idx - 1) {
             self.indices.push(begin_idx);
@@ -58,48 +78,203 @@ impl BrushMeshInfo {
         }
     }
 
-    pub fn to_mesh(&self, centroid: Vec3, tex_size: Vec2) -> Mesh {
-        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
-
-        mesh.insert_attribute(
-            Mesh::ATTRIBUTE_POSITION,
-            self.vertices
-                .iter()
-                .map(|v| map_to_bevy_space3(&((*v - centroid) * super::SCALE)))
-                .collect::<Vec<_>>(),
-        );
-
-        mesh.set_indices(Some(Indices::U32(
-            self.indices
-                .iter()
-                .map(|i| *i as u32) // TODO: Smelly
-                .collect::<Vec<_>>(),
-        )));
-
-        mesh.insert_attribute(
-            Mesh::ATTRIBUTE_NORMAL,
-            self.normals
-                .iter()
-                .map(map_to_bevy_space3)
-                .collect::<Vec<_>>(),
-        );
-
-        mesh.insert_attribute(
-            Mesh::ATTRIBUTE_TANGENT,
-            self.tangents
-                .iter()
-                .map(map_to_bevy_space4)
-                .collect::<Vec<_>>(),
-        );
-
-        mesh.insert_attribute(
-            Mesh::ATTRIBUTE_UV_0,
-            self.uvs
-                .iter()
-                .map(|uv| (*uv / tex_size).to_array())
-                .collect::<Vec<_>>(),
-        );
-
-        mesh
+    pub fn centroid(&self) -> Vec3 {
+        self.vertices.iter().sum::<Vec3>() / (self.vertices.len() as f32)
+    }
+
+    /// Packs a chart per face into a lightmap atlas, returning the UVs of each vertex and the atlas size
+    pub fn lightmap_uvs(&self, settings: &LightmapSettings) -> (Vec<Vec2>, MeshLightmap) {
+        super::pack_lightmap(&self.lightmap_coords, &self.charts, settings)
+    }
+
+    pub fn to_mesh(&self, centroid: Vec3, tex_size: Vec2, settings: &MapLoaderSettings) -> Mesh {
+        let uvs = self.uvs.iter().map(|uv| *uv / tex_size);
+
+        build_mesh(
+            &self.vertices,
+            &self.indices,
+            &self.normals,
+            &self.tangents,
+            uvs,
+            centroid,
+            settings,
+        )
+    }
+}
+
+/// Representation of the mesh of a tessellated patch. Coordinates are in .map space, like `BrushMeshInfo`.
+/// Unlike brushes, patch UVs are already normalized to the texture size.
+#[derive(Debug, Default)]
+pub struct PatchMeshInfo {
+    vertices: Vec<Vec3>,
+    indices: Vec<usize>,
+    normals: Vec<Vec3>,
+    uvs: Vec<Vec2>,
+    tangents: Vec<Vec4>,
+}
+
+impl PatchMeshInfo {
+    /// Triangulates a tessellated patch, facing the direction of `d/dx × d/dy` as in Quake 3
+    pub fn new(patch: &Patch) -> Self {
+        let width = patch.width;
+
+        let vertices = patch
+            .control_points
+            .iter()
+            .map(|p| p.position.as_vec3())
+            .collect::<Vec<_>>();
+
+        let uvs = patch
+            .control_points
+            .iter()
+            .map(|p| p.uv.as_vec2())
+            .collect::<Vec<_>>();
+
+        let mut indices = Vec::new();
+
+        for y in 0..(patch.height - 1) {
+            for x in 0..(width - 1) {
+                let idx = y * width + x;
+
+                indices.extend([idx, idx + 1, idx + width]);
+                indices.extend([idx + 1, idx + width + 1, idx + width]);
+            }
+        }
+
+        // Accumulate area-weighted normals and tangents from each triangle
+        let mut normals = vec![Vec3::ZERO; vertices.len()];
+        let mut tangents = vec![Vec3::ZERO; vertices.len()];
+        let mut bitangents = vec![Vec3::ZERO; vertices.len()];
+
+        for triangle in indices.chunks_exact(3) {
+            let (a, b, c) = (triangle[0], triangle[1], triangle[2]);
+
+            let edge1 = vertices[b] - vertices[a];
+            let edge2 = vertices[c] - vertices[a];
+            let uv1 = uvs[b] - uvs[a];
+            let uv2 = uvs[c] - uvs[a];
+
+            let normal = edge1.cross(edge2);
+            let det = uv1.x * uv2.y - uv2.x * uv1.y;
+
+            let (tangent, bitangent) = if det.abs() > f32::EPSILON {
+                (
+                    (edge1 * uv2.y - edge2 * uv1.y) / det,
+                    (edge2 * uv1.x - edge1 * uv2.x) / det,
+                )
+            } else {
+                (Vec3::ZERO, Vec3::ZERO)
+            };
+
+            for idx in triangle {
+                normals[*idx] += normal;
+                tangents[*idx] += tangent;
+                bitangents[*idx] += bitangent;
+            }
+        }
+
+        let normals = normals
+            .into_iter()
+            .map(|n| n.normalize_or_zero())
+            .collect::<Vec<_>>();
+
+        let tangents = tangents
+            .iter()
+            .zip(&bitangents)
+            .zip(&normals)
+            .map(|((tangent, bitangent), normal)| {
+                // Gram-Schmidt orthogonalization
+                let t = (*tangent - *normal * normal.dot(*tangent)).normalize_or_zero();
+                let w = if normal.cross(t).dot(*bitangent) < 0.0 {
+                    -1.0
+                } else {
+                    1.0
+                };
+
+                t.extend(w)
+            })
+            .collect();
+
+        Self {
+            vertices,
+            indices,
+            normals,
+            uvs,
+            tangents,
+        }
+    }
+
+    pub fn centroid(&self) -> Vec3 {
+        self.vertices.iter().sum::<Vec3>() / (self.vertices.len() as f32)
     }
+
+    pub fn to_mesh(&self, centroid: Vec3, settings: &MapLoaderSettings) -> Mesh {
+        build_mesh(
+            &self.vertices,
+            &self.indices,
+            &self.normals,
+            &self.tangents,
+            self.uvs.iter().copied(),
+            centroid,
+            settings,
+        )
+    }
+}
+
+/// Converts mesh data in .map space to a Bevy mesh, with positions relative to `centroid`
+fn build_mesh(
+    vertices: &[Vec3],
+    indices: &[usize],
+    normals: &[Vec3],
+    tangents: &[Vec4],
+    uvs: impl Iterator<Item = Vec2>,
+    centroid: Vec3,
+    settings: &MapLoaderSettings,
+) -> Mesh {
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+
+    mesh.insert_attribute(
+        Mesh::ATTRIBUTE_POSITION,
+        vertices
+            .iter()
+            .map(|v| map_to_bevy_position(&(*v - centroid), settings).to_array())
+            .collect::<Vec<_>>(),
+    );
+
+    let mut indices = indices
+        .iter()
+        .map(|i| *i as u32) // TODO: Smelly
+        .collect::<Vec<_>>();
+
+    // Mirroring flips the winding order, so triangles would face inwards
+    if map_is_mirrored(settings) {
+        for triangle in indices.chunks_exact_mut(3) {
+            triangle.swap(1, 2);
+        }
+    }
+
+    mesh.set_indices(Some(Indices::U32(indices)));
+
+    mesh.insert_attribute(
+        Mesh::ATTRIBUTE_NORMAL,
+        normals
+            .iter()
+            .map(|n| map_to_bevy_space3(n, settings).to_array())
+            .collect::<Vec<_>>(),
+    );
+
+    mesh.insert_attribute(
+        Mesh::ATTRIBUTE_TANGENT,
+        tangents
+            .iter()
+            .map(|t| map_to_bevy_space4(t, settings).to_array())
+            .collect::<Vec<_>>(),
+    );
+
+    mesh.insert_attribute(
+        Mesh::ATTRIBUTE_UV_0,
+        uvs.map(|uv| uv.to_array()).collect::<Vec<_>>(),
+    );
+
+    mesh
 }
@@ -0,0 +1,87 @@
+use super::{
+    get_supported_compressed_formats, load_map, FileAssetProvider, MapAssetProvider,
+    MapEntitySpawners, MapLoaderSettings, SharedMapTextures,
+};
+use anyhow::Result as AResult;
+use bevy::{
+    asset::{AssetLoader, BoxedFuture, LoadContext},
+    prelude::{FromWorld, World},
+    render::texture::CompressedImageFormats,
+};
+use std::sync::Arc;
+
+/// The `MapAssetProvider` used by `MapAssetLoader`.
+/// Insert this resource before adding `MapPlugin` to use a custom provider,
+/// otherwise a `FileAssetProvider` is used.
+#[derive(Clone)]
+pub struct MapAssetProviderResource(pub Arc<dyn MapAssetProvider>);
+
+impl MapAssetProviderResource {
+    pub fn new(provider: impl MapAssetProvider + 'static) -> Self {
+        Self(Arc::new(provider))
+    }
+}
+
+/// Loads .map files as scenes. Registered by `MapPlugin`.
+///
+/// The provider and `MapLoaderSettings` are read from the world when the loader is created,
+/// so they must be inserted before `MapPlugin` is added. Entity spawners can be registered at any time.
+pub struct MapAssetLoader {
+    asset_provider: Arc<dyn MapAssetProvider>,
+    supported_compressed_formats: CompressedImageFormats,
+    spawners: MapEntitySpawners,
+    settings: MapLoaderSettings,
+    shared_textures: SharedMapTextures,
+}
+
+impl FromWorld for MapAssetLoader {
+    fn from_world(world: &mut World) -> Self {
+        let asset_provider = match world.get_resource::<MapAssetProviderResource>() {
+            Some(provider) => provider.0.clone(),
+            None => Arc::new(FileAssetProvider::from_world(world)),
+        };
+
+        Self {
+            asset_provider,
+            supported_compressed_formats: get_supported_compressed_formats(world),
+            spawners: world
+                .get_resource_or_insert_with(MapEntitySpawners::default)
+                .clone(),
+            settings: world
+                .get_resource_or_insert_with(MapLoaderSettings::default)
+                .clone(),
+            shared_textures: world
+                .get_resource_or_insert_with(SharedMapTextures::default)
+                .clone(),
+        }
+    }
+}
+
+impl AssetLoader for MapAssetLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, AResult<()>> {
+        Box::pin(async move {
+            let map = load_map(
+                bytes,
+                load_context,
+                self.supported_compressed_formats,
+                self.asset_provider.clone(),
+                &self.spawners,
+                &self.settings,
+                &self.shared_textures,
+            )
+            .await?;
+
+            load_context.set_default_asset(map);
+
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["map"]
+    }
+}
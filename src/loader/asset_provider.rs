@@ -1,14 +1,23 @@
+use crate::{
+    game_config::FilesystemSettings,
+    texture_formats::{DecodedTexture, IndexedImageFormat, Palette, TextureFormatError},
+    vfs::{Archive, Vfs},
+};
 use anyhow::Result as AResult;
 use bevy::{
-    asset::LoadContext,
+    asset::{AssetPath, LoadContext},
     pbr::StandardMaterial,
     prelude::{AssetServer, FromWorld, Image, World},
     render::{
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
         renderer::RenderDevice,
         texture::{CompressedImageFormats, ImageType},
     },
 };
-use std::path::{Path, PathBuf};
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -27,12 +36,37 @@ pub enum FileTextureLoadError {
     NoExtension,
 }
 
+/// A texture loaded by a `MapAssetProvider`
+pub struct MapTexture {
+    /// The decoded texture, used to scale UVs and create default materials
+    pub image: Image,
+    /// The asset path the texture was read from, if the `AssetServer` can load it.
+    /// Default materials then share this asset instead of embedding a copy of `image`,
+    /// so it is hot-reloaded when it changes.
+    pub path: Option<AssetPath<'static>>,
+}
+
+impl MapTexture {
+    /// A texture which is embedded in each map using it
+    pub fn embedded(image: Image) -> Self {
+        Self { image, path: None }
+    }
+}
+
 /// A relatively versatile and sensible default `MapAssetProvider`.
 /// Uses the current `AssetIo` to load textures from the directory defined in the map's `worldspawn` entity.
 /// Supports any file extension, as long as Bevy can load it.
+/// Textures are loaded as shared assets, so they are hot-reloaded and shared between maps.
+///
+/// With `with_packages`, textures which are not found on disk are also looked up in the game's packages.
+/// Quake `.lmp` and Quake 2 `.wal` textures are decoded with the palette at `palette_path`.
 pub struct FileAssetProvider {
     asset_server: AssetServer,
     default_texture_path: String,
+    palette_path: String,
+    packages: Option<FilesystemSettings>,
+    vfs: RwLock<Option<Arc<Vfs>>>,
+    palette: RwLock<Option<Arc<Palette>>>,
 }
 
 impl FromWorld for FileAssetProvider {
@@ -42,45 +76,215 @@ impl FromWorld for FileAssetProvider {
         Self {
             asset_server,
             default_texture_path: "textures/default.png".to_string(),
+            palette_path: "gfx/palette.lmp".to_string(),
+            packages: None,
+            vfs: Default::default(),
+            palette: Default::default(),
         }
     }
 }
 
 impl FileAssetProvider {
-    async fn try_load_texture_path(
+    /// Mounts the packages in `filesystem.search_path` (relative to the asset root) when textures are first loaded
+    pub fn with_packages(mut self, filesystem: FilesystemSettings) -> Self {
+        self.packages = Some(filesystem);
+        self
+    }
+
+    /// Sets the palette used by indexed textures, such as `TextureSettings::palette` from the game config.
+    /// Either raw colors or a PCX image, relative to the asset root or inside the packages.
+    pub fn with_palette(mut self, path: impl Into<String>) -> Self {
+        self.palette_path = path.into();
+        self
+    }
+
+    pub(crate) async fn load_palette(&self) -> Option<Arc<Palette>> {
+        if let Some(palette) = self.palette.read().unwrap().as_ref() {
+            return Some(palette.clone());
+        }
+
+        let path = Path::new(&self.palette_path);
+
+        let bytes = match self.asset_server.asset_io().load_path(path).await {
+            Ok(bytes) => bytes,
+            Err(_) => self.mount_packages().await?.read_file(path)?.ok()?,
+        };
+
+        let extension = path.extension().and_then(|ext| ext.to_str());
+        let palette = match Palette::from_file(extension, &bytes) {
+            Ok(palette) => Arc::new(palette),
+            Err(err) => {
+                bevy::log::warn!("failed to read palette {}: {}", self.palette_path, err);
+                return None;
+            }
+        };
+
+        *self.palette.write().unwrap() = Some(palette.clone());
+
+        Some(palette)
+    }
+
+    /// Decodes an image file, using the palette for indexed formats
+    async fn decode_image(
         &self,
         path: &Path,
+        buf: &[u8],
         supported_compressed_formats: CompressedImageFormats,
     ) -> AResult<Image> {
-        let image_type = ImageType::Extension(
-            path.extension()
-                .ok_or(FileTextureLoadError::NoExtension)?
-                .to_str()
-                .unwrap(),
-        );
+        let extension = path
+            .extension()
+            .ok_or(FileTextureLoadError::NoExtension)?
+            .to_str()
+            .unwrap();
 
-        let buf = self.asset_server.asset_io().load_path(path).await?;
+        if let Some(format) = IndexedImageFormat::from_extension(extension) {
+            let palette = self
+                .load_palette()
+                .await
+                .ok_or(TextureFormatError::MissingPalette)?;
+
+            return Ok(to_image(format.decode(buf, &palette)?));
+        }
 
         Ok(Image::from_buffer(
-            &buf,
-            image_type,
+            buf,
+            ImageType::Extension(extension),
             supported_compressed_formats,
             true,
         )?)
     }
 
+    async fn mount_packages(&self) -> Option<Arc<Vfs>> {
+        let settings = self.packages.as_ref()?;
+
+        if let Some(vfs) = self.vfs.read().unwrap().as_ref() {
+            return Some(vfs.clone());
+        }
+
+        let asset_io = self.asset_server.asset_io();
+        let files = asset_io
+            .read_directory(Path::new(&settings.search_path))
+            .ok()?;
+
+        let mut vfs = Vfs::default();
+
+        for (path, format) in Vfs::package_paths(settings, files) {
+            let archive = match asset_io.load_path(&path).await {
+                Ok(bytes) => Archive::parse(format, bytes),
+                Err(err) => {
+                    bevy::log::warn!("failed to read {}: {}", path.display(), err);
+                    continue;
+                }
+            };
+
+            match archive {
+                Ok(archive) => vfs.mount(archive),
+                Err(err) => bevy::log::warn!("failed to mount {}: {}", path.display(), err),
+            }
+        }
+
+        let vfs = Arc::new(vfs);
+        *self.vfs.write().unwrap() = Some(vfs.clone());
+
+        Some(vfs)
+    }
+
+    async fn try_load_packaged_texture(
+        &self,
+        texture_path: &Path,
+        supported_compressed_formats: CompressedImageFormats,
+    ) -> AResult<MapTexture> {
+        let vfs = self
+            .mount_packages()
+            .await
+            .ok_or(FileTextureLoadError::FileNotFound)?;
+
+        let directory = texture_path
+            .parent()
+            .ok_or(FileTextureLoadError::DirectoryParseFailed)?;
+
+        let filename_without_ext = texture_path
+            .file_name()
+            .ok_or(FileTextureLoadError::FilenameParseFailed)?;
+
+        let file = vfs
+            .read_directory(directory)
+            .into_iter()
+            .find(|p| p.file_stem() == Some(filename_without_ext))
+            .ok_or(FileTextureLoadError::FileNotFound)?;
+
+        let buf = vfs
+            .read_file(&file)
+            .ok_or(FileTextureLoadError::FileNotFound)??;
+
+        Ok(MapTexture::embedded(
+            self.decode_image(&file, &buf, supported_compressed_formats)
+                .await?,
+        ))
+    }
+
+    async fn try_load_texture_path(
+        &self,
+        path: &Path,
+        supported_compressed_formats: CompressedImageFormats,
+    ) -> AResult<MapTexture> {
+        let buf = self.asset_server.asset_io().load_path(path).await?;
+        let image = self
+            .decode_image(path, &buf, supported_compressed_formats)
+            .await?;
+
+        // Bevy has no loaders for indexed formats, so they can't be shared assets
+        let is_indexed = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(IndexedImageFormat::from_extension)
+            .is_some();
+
+        Ok(MapTexture {
+            image,
+            path: (!is_indexed).then(|| AssetPath::new(path.to_path_buf(), None)),
+        })
+    }
+
     pub async fn try_load_texture(
         &self,
         load_context: &mut LoadContext<'_>,
         texture_collections: Option<&[&str]>,
         supported_compressed_formats: CompressedImageFormats,
         tex_name: &str,
-    ) -> AResult<Image> {
+    ) -> AResult<MapTexture> {
         let collection_dir = *texture_collections
             .ok_or(FileTextureLoadError::MissingTextureCollections)?
             .get(0)
             .ok_or(FileTextureLoadError::EmptyTextureCollections)?;
 
+        // Packages are relative to the game directory rather than the map
+        let packaged_path = Path::new(collection_dir).join(tex_name);
+
+        match self
+            .try_load_texture_file(
+                load_context,
+                collection_dir,
+                tex_name,
+                supported_compressed_formats,
+            )
+            .await
+        {
+            Ok(texture) => Ok(texture),
+            Err(err) => self
+                .try_load_packaged_texture(&packaged_path, supported_compressed_formats)
+                .await
+                .map_err(|_| err),
+        }
+    }
+
+    async fn try_load_texture_file(
+        &self,
+        load_context: &mut LoadContext<'_>,
+        collection_dir: &str,
+        tex_name: &str,
+        supported_compressed_formats: CompressedImageFormats,
+    ) -> AResult<MapTexture> {
         let mut texture_path = PathBuf::new();
 
         if let Some(path) = load_context.path().parent() {
@@ -116,7 +320,7 @@ impl MapAssetProvider for FileAssetProvider {
         _load_context: &mut LoadContext,
         _texture_collections: Option<&[&str]>,
         supported_compressed_formats: CompressedImageFormats,
-    ) -> AResult<Image> {
+    ) -> AResult<MapTexture> {
         self.try_load_texture_path(
             Path::new(&self.default_texture_path),
             supported_compressed_formats,
@@ -130,7 +334,7 @@ impl MapAssetProvider for FileAssetProvider {
         texture_collections: Option<&[&str]>,
         supported_compressed_formats: CompressedImageFormats,
         tex_name: &str,
-    ) -> Option<Image> {
+    ) -> Option<MapTexture> {
         self.try_load_texture(
             load_context,
             texture_collections,
@@ -142,6 +346,19 @@ impl MapAssetProvider for FileAssetProvider {
     }
 }
 
+pub(crate) fn to_image(texture: DecodedTexture) -> Image {
+    Image::new(
+        Extent3d {
+            width: texture.width,
+            height: texture.height,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        texture.data,
+        TextureFormat::Rgba8UnormSrgb,
+    )
+}
+
 pub fn get_supported_compressed_formats(world: &mut World) -> CompressedImageFormats {
     match world.get_resource::<RenderDevice>() {
         Some(render_device) => CompressedImageFormats::from_features(render_device.features()),
@@ -156,14 +373,14 @@ pub trait MapAssetProvider: Send + Sync {
         load_context: &mut LoadContext,
         texture_collections: Option<&[&str]>,
         supported_compressed_formats: CompressedImageFormats,
-    ) -> AResult<Image>;
+    ) -> AResult<MapTexture>;
 
     async fn load_missing_texture(
         &self,
         load_context: &mut LoadContext,
         texture_collections: Option<&[&str]>,
         supported_compressed_formats: CompressedImageFormats,
-    ) -> AResult<Image> {
+    ) -> AResult<MapTexture> {
         self.load_default_texture(
             load_context,
             texture_collections,
@@ -180,7 +397,7 @@ pub trait MapAssetProvider: Send + Sync {
         texture_collections: Option<&[&str]>,
         supported_compressed_formats: CompressedImageFormats,
         tex_name: &str,
-    ) -> Option<Image>;
+    ) -> Option<MapTexture>;
 
     /// Create a material from the information provided,
     /// or use the default one if `None` is returned.
@@ -0,0 +1,202 @@
+#[cfg(feature = "rapier")]
+use super::Brush;
+use super::MapTags;
+use crate::map_data::Entity as EntityData;
+use bevy::prelude::*;
+#[cfg(feature = "rapier")]
+use bevy_rapier3d::prelude::{ActiveEvents, Collider, RigidBody, Sensor};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// How the brushes of a map entity collide
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Reflect, Serialize, Deserialize)]
+#[reflect_value(PartialEq, Serialize, Deserialize)]
+pub enum ColliderPolicy {
+    /// No colliders
+    None,
+    /// A sensor per brush, without a rigid body (e.g. triggers)
+    Sensor,
+    /// A fixed rigid body per brush
+    #[default]
+    Fixed,
+    /// A kinematic rigid body for the whole entity, with a collider per brush (e.g. doors and platforms)
+    Kinematic,
+    /// A fixed rigid body for the whole entity, with a single compound collider of all its brushes
+    Compound,
+}
+
+/// Chooses the `ColliderPolicy` of each map entity.
+/// Classnames take priority over brush tags (see `MapTags`), which take priority over the default.
+///
+/// By default, entities matching the `Trigger` tag are sensors, and everything else is fixed.
+#[derive(Clone, Debug)]
+pub struct MapColliders {
+    pub default: ColliderPolicy,
+    classnames: HashMap<String, ColliderPolicy>,
+    tags: HashMap<String, ColliderPolicy>,
+}
+
+impl Default for MapColliders {
+    fn default() -> Self {
+        let mut colliders = Self {
+            default: ColliderPolicy::Fixed,
+            classnames: HashMap::new(),
+            tags: HashMap::new(),
+        };
+
+        colliders.set_tag("Trigger", ColliderPolicy::Sensor);
+        colliders
+    }
+}
+
+impl MapColliders {
+    /// Sets the policy of entities with the given classname
+    pub fn set_classname(
+        &mut self,
+        classname: impl Into<String>,
+        policy: ColliderPolicy,
+    ) -> &mut Self {
+        self.classnames.insert(classname.into(), policy);
+        self
+    }
+
+    /// Sets the policy of entities matching the brush tag called `name`
+    pub fn set_tag(&mut self, name: impl Into<String>, policy: ColliderPolicy) -> &mut Self {
+        self.tags.insert(name.into(), policy);
+        self
+    }
+
+    pub fn policy(&self, entity: &EntityData, tags: &MapTags) -> ColliderPolicy {
+        entity
+            .classname()
+            .and_then(|classname| self.classnames.get(classname))
+            .or_else(|| tags.brush_tag(entity).and_then(|name| self.tags.get(name)))
+            .copied()
+            .unwrap_or(self.default)
+    }
+}
+
+/// Sent when a brush's collider can't be built, usually because its vertices are coplanar
+#[cfg(feature = "rapier")]
+#[derive(Clone, Copy, Debug)]
+pub struct DegenerateBrushHull {
+    pub brush: Entity,
+}
+
+#[cfg(feature = "rapier")]
+fn brush_hull(
+    entity: Entity,
+    brush: &Brush,
+    degenerate: &mut EventWriter<DegenerateBrushHull>,
+) -> Option<Collider> {
+    let hull = Collider::convex_hull(&brush.all_vertices);
+
+    if hull.is_none() {
+        warn!("brush {:?} has a degenerate convex hull", entity);
+        degenerate.send(DegenerateBrushHull { brush: entity });
+    }
+
+    hull
+}
+
+/// Builds the colliders and rigid bodies of newly spawned brushes, according to their `ColliderPolicy`
+#[cfg(feature = "rapier")]
+pub fn spawn_map_colliders(
+    mut commands: Commands,
+    query: Query<(Entity, &Brush, &Transform, Option<&Parent>), Added<Brush>>,
+    mut degenerate: EventWriter<DegenerateBrushHull>,
+) {
+    let mut compounds: HashMap<Entity, Vec<_>> = HashMap::new();
+
+    for (entity, brush, transform, parent) in query.iter() {
+        if brush.collider == ColliderPolicy::None {
+            continue;
+        }
+
+        let hull = match brush_hull(entity, brush, &mut degenerate) {
+            Some(hull) => hull,
+            None => continue,
+        };
+
+        match (brush.collider, parent) {
+            (ColliderPolicy::Sensor, _) => {
+                commands
+                    .entity(entity)
+                    .insert(hull)
+                    .insert(Sensor(true))
+                    .insert(ActiveEvents::COLLISION_EVENTS);
+            }
+            // Colliders attach to the nearest rigid body among their ancestors
+            (ColliderPolicy::Kinematic, Some(parent)) => {
+                commands.entity(entity).insert(hull);
+                commands
+                    .entity(parent.0)
+                    .insert(RigidBody::KinematicPositionBased);
+            }
+            // Brushes of the same entity are spawned together, so they can be gathered in one pass
+            (ColliderPolicy::Compound, Some(parent)) => {
+                compounds.entry(parent.0).or_default().push((
+                    transform.translation,
+                    transform.rotation,
+                    hull,
+                ));
+            }
+            _ => {
+                commands
+                    .entity(entity)
+                    .insert(hull)
+                    .insert(RigidBody::Fixed);
+            }
+        }
+    }
+
+    for (parent, shapes) in compounds {
+        commands
+            .entity(parent)
+            .insert(Collider::compound(shapes))
+            .insert(RigidBody::Fixed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ColliderPolicy, MapColliders};
+    use crate::{loader::MapTags, map_data::Entity as EntityData};
+    use std::collections::HashMap;
+
+    fn entity(classname: &str) -> EntityData {
+        EntityData {
+            properties: HashMap::from([("classname".to_string(), classname.to_string())]),
+            brushes: Vec::new(),
+            patches: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_policy() {
+        let tags = MapTags::default();
+        let mut colliders = MapColliders::default();
+
+        assert_eq!(
+            colliders.policy(&entity("worldspawn"), &tags),
+            ColliderPolicy::Fixed
+        );
+        assert_eq!(
+            colliders.policy(&entity("trigger_once"), &tags),
+            ColliderPolicy::Sensor
+        );
+
+        colliders
+            .set_classname("func_door", ColliderPolicy::Kinematic)
+            .set_classname("trigger_hurt", ColliderPolicy::None);
+
+        assert_eq!(
+            colliders.policy(&entity("func_door"), &tags),
+            ColliderPolicy::Kinematic
+        );
+        assert_eq!(
+            colliders.policy(&entity("trigger_hurt"), &tags),
+            ColliderPolicy::None
+        );
+    }
+}
@@ -0,0 +1,333 @@
+use super::{MapTags, TagBehavior};
+use crate::map_data::{
+    clip_polygon, polygon_area, Brush as BrushData, BrushGeometry, Entity as EntityData,
+};
+use bevy::prelude::{Component, Reflect, ReflectComponent};
+use glam::DVec3;
+use std::collections::HashMap;
+
+/// Size of the cells used to find coincident vertices, in .map units
+const WELD_CELL_SIZE: f64 = 1.0;
+
+/// Statistics of the brush merging pass (see `MapLoaderSettings::merge_brushes`),
+/// added to the root entity of each map scene.
+#[derive(Component, Default, Reflect, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct MapMeshStats {
+    /// Vertices moved onto a coincident vertex of another brush
+    pub vertices_welded: usize,
+    /// Faces completely covered by other brushes
+    pub faces_culled: usize,
+    /// Triangles of the culled faces
+    pub triangles_culled: usize,
+}
+
+/// Welds the vertices of an entity's brushes, and finds their hidden faces (see `hidden_faces`)
+pub(crate) fn merge_brushes(
+    entity: &EntityData,
+    geometries: &mut [BrushGeometry],
+    tags: &MapTags,
+    stats: &mut MapMeshStats,
+) -> Vec<Vec<bool>> {
+    stats.vertices_welded += weld_vertices(geometries);
+
+    let hidden = hidden_faces(entity, geometries, tags);
+
+    for ((brush, geometry), hidden) in entity.brushes.iter().zip(geometries.iter()).zip(&hidden) {
+        for (face_idx, face) in brush.faces.iter().enumerate() {
+            if hidden[face_idx] && tags.face_behavior(entity, face) == TagBehavior::Render {
+                stats.faces_culled += 1;
+                stats.triangles_culled += geometry.faces[face_idx].len() - 2;
+            }
+        }
+    }
+
+    hidden
+}
+
+/// Moves vertices which nearly coincide onto the same position, so brushes meet without cracks.
+/// Returns the number of vertices moved.
+pub(crate) fn weld_vertices(geometries: &mut [BrushGeometry]) -> usize {
+    let mut cells: HashMap<[i64; 3], Vec<DVec3>> = HashMap::new();
+    let mut welded = 0;
+
+    for vertex in geometries.iter_mut().flat_map(|g| g.vertices.iter_mut()) {
+        let cell = (*vertex / WELD_CELL_SIZE)
+            .floor()
+            .to_array()
+            .map(|c| c as i64);
+
+        // Vertices within epsilon may be in a neighboring cell
+        let existing = (-1..=1)
+            .flat_map(|x| (-1..=1).flat_map(move |y| (-1..=1).map(move |z| [x, y, z])))
+            .filter_map(|offset| {
+                cells.get(&[
+                    cell[0] + offset[0],
+                    cell[1] + offset[1],
+                    cell[2] + offset[2],
+                ])
+            })
+            .flatten()
+            .find(|v| v.abs_diff_eq(*vertex, crate::EPSILON_64))
+            .copied();
+
+        match existing {
+            Some(existing) => {
+                if existing != *vertex {
+                    *vertex = existing;
+                    welded += 1;
+                }
+            }
+            None => cells.entry(cell).or_default().push(*vertex),
+        }
+    }
+
+    welded
+}
+
+/// Finds the faces of an entity's brushes which are completely inside other brushes.
+/// Only brushes with every face rendered and opaque hide faces,
+/// so tool brushes and liquids don't leave holes.
+///
+/// Of two faces on the same plane facing the same way, the one of the earlier brush is kept.
+pub(crate) fn hidden_faces(
+    entity: &EntityData,
+    geometries: &[BrushGeometry],
+    tags: &MapTags,
+) -> Vec<Vec<bool>> {
+    let brushes = &entity.brushes;
+
+    let occluders = brushes
+        .iter()
+        .map(|brush| {
+            brush.faces.iter().all(|face| {
+                tags.face_behavior(entity, face) == TagBehavior::Render
+                    && !tags.is_translucent(entity, face)
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let brush_bounds = geometries
+        .iter()
+        .map(|g| bounds(&g.vertices))
+        .collect::<Vec<_>>();
+
+    let planes = brushes.iter().map(BrushData::planes).collect::<Vec<_>>();
+
+    let mut hidden = Vec::with_capacity(brushes.len());
+
+    for (brush_idx, geometry) in geometries.iter().enumerate() {
+        let faces = (0..geometry.faces.len())
+            .map(|face_idx| {
+                let polygon = geometry.face_vertices(face_idx).collect::<Vec<_>>();
+
+                if polygon.is_empty() {
+                    return false;
+                }
+
+                let face_bounds = bounds(&polygon);
+                let mut fragments = vec![polygon];
+
+                for (other_idx, other_planes) in planes.iter().enumerate() {
+                    if other_idx == brush_idx
+                        || !occluders[other_idx]
+                        || !overlaps(face_bounds, brush_bounds[other_idx])
+                    {
+                        continue;
+                    }
+
+                    fragments = fragments
+                        .into_iter()
+                        .flat_map(|fragment| {
+                            outside_fragments(
+                                fragment,
+                                planes[brush_idx][face_idx],
+                                other_planes,
+                                other_idx < brush_idx,
+                            )
+                        })
+                        .collect();
+
+                    if fragments.is_empty() {
+                        return true;
+                    }
+                }
+
+                false
+            })
+            .collect();
+
+        hidden.push(faces);
+    }
+
+    hidden
+}
+
+/// Splits a face's polygon by a brush, returning the parts outside of it.
+/// If `brush_is_earlier`, the brush's faces on the same plane (and facing the same way) win.
+fn outside_fragments(
+    polygon: Vec<DVec3>,
+    (face_normal, _): (DVec3, f64),
+    brush_planes: &[(DVec3, f64)],
+    brush_is_earlier: bool,
+) -> Vec<Vec<DVec3>> {
+    let mut inside = polygon.clone();
+    let mut outside = Vec::new();
+
+    for (normal, dist) in brush_planes {
+        let on_plane = inside
+            .iter()
+            .all(|v| (normal.dot(*v) - dist).abs() <= crate::EPSILON_64);
+
+        if on_plane {
+            // Touching faces facing each other are always hidden
+            if normal.dot(face_normal) > 0.0 && !brush_is_earlier {
+                return vec![polygon];
+            }
+
+            continue;
+        }
+
+        let front = clip_polygon(&inside, -*normal, -dist);
+        inside = clip_polygon(&inside, *normal, *dist);
+
+        if polygon_area(&inside, face_normal) <= crate::EPSILON_64 {
+            return vec![polygon];
+        }
+
+        if polygon_area(&front, face_normal) > crate::EPSILON_64 {
+            outside.push(front);
+        }
+    }
+
+    outside
+}
+
+fn bounds(vertices: &[DVec3]) -> (DVec3, DVec3) {
+    vertices.iter().fold(
+        (DVec3::splat(f64::MAX), DVec3::splat(f64::MIN)),
+        |(min, max), v| (min.min(*v), max.max(*v)),
+    )
+}
+
+fn overlaps((min_a, max_a): (DVec3, DVec3), (min_b, max_b): (DVec3, DVec3)) -> bool {
+    (min_a - crate::EPSILON_64).cmple(max_b).all() && (min_b - crate::EPSILON_64).cmple(max_a).all()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{hidden_faces, weld_vertices};
+    use crate::{loader::MapTags, parsing::parse_map};
+    use glam::DVec3;
+
+    const BRUSHES: &str = r#"
+{
+"classname" "worldspawn"
+{
+( 0 0 0 ) ( 0 1 0 ) ( 0 0 1 ) wall [ 0 1 0 0 ] [ 0 0 -1 0 ] 0 1 1
+( 0 0 0 ) ( 0 0 1 ) ( 1 0 0 ) wall [ 1 0 0 0 ] [ 0 0 -1 0 ] 0 1 1
+( 0 0 0 ) ( 1 0 0 ) ( 0 1 0 ) wall [ 1 0 0 0 ] [ 0 -1 0 0 ] 0 1 1
+( 64 64 64 ) ( 64 65 64 ) ( 65 64 64 ) wall [ 1 0 0 0 ] [ 0 -1 0 0 ] 0 1 1
+( 64 64 64 ) ( 65 64 64 ) ( 64 64 65 ) wall [ 1 0 0 0 ] [ 0 0 -1 0 ] 0 1 1
+( 64 64 64 ) ( 64 64 65 ) ( 64 65 64 ) wall [ 0 1 0 0 ] [ 0 0 -1 0 ] 0 1 1
+}
+{
+( 64 0 0 ) ( 64 1 0 ) ( 64 0 1 ) wall [ 0 1 0 0 ] [ 0 0 -1 0 ] 0 1 1
+( 64 0 0 ) ( 64 0 1 ) ( 65 0 0 ) wall [ 1 0 0 0 ] [ 0 0 -1 0 ] 0 1 1
+( 64 0 0 ) ( 65 0 0 ) ( 64 1 0 ) wall [ 1 0 0 0 ] [ 0 -1 0 0 ] 0 1 1
+( 128 64 64 ) ( 128 65 64 ) ( 129 64 64 ) wall [ 1 0 0 0 ] [ 0 -1 0 0 ] 0 1 1
+( 128 64 64 ) ( 129 64 64 ) ( 128 64 65 ) wall [ 1 0 0 0 ] [ 0 0 -1 0 ] 0 1 1
+( 128 64 64 ) ( 128 64 65 ) ( 128 65 64 ) wall [ 0 1 0 0 ] [ 0 0 -1 0 ] 0 1 1
+}
+{
+( 64 0 0 ) ( 64 1 0 ) ( 64 0 1 ) wall [ 0 1 0 0 ] [ 0 0 -1 0 ] 0 1 1
+( 64 0 0 ) ( 64 0 1 ) ( 65 0 0 ) wall [ 1 0 0 0 ] [ 0 0 -1 0 ] 0 1 1
+( 64 0 0 ) ( 65 0 0 ) ( 64 1 0 ) wall [ 1 0 0 0 ] [ 0 -1 0 0 ] 0 1 1
+( 96 32 32 ) ( 96 33 32 ) ( 97 32 32 ) wall [ 1 0 0 0 ] [ 0 -1 0 0 ] 0 1 1
+( 96 32 32 ) ( 97 32 32 ) ( 96 32 33 ) wall [ 1 0 0 0 ] [ 0 0 -1 0 ] 0 1 1
+( 96 32 32 ) ( 96 32 33 ) ( 96 33 32 ) clip [ 0 1 0 0 ] [ 0 0 -1 0 ] 0 1 1
+}
+}"#;
+
+    #[test]
+    fn test_hidden_faces() {
+        let map = parse_map::<()>(BRUSHES).expect("failed to parse").1;
+        let entity = &map.entities[0];
+
+        let geometries = entity
+            .brushes
+            .iter()
+            .map(|brush| brush.geometry().expect("brush is degenerate"))
+            .collect::<Vec<_>>();
+
+        let hidden = hidden_faces(entity, &geometries, &MapTags::default());
+
+        // The +X face of the first brush touches the second brush
+        assert_eq!(hidden[0], vec![false, false, false, false, false, true]);
+        // The first and second brushes touch
+        assert_eq!(hidden[1], vec![true, false, false, false, false, false]);
+        // The third brush is inside the second, except for the faces it shares with it
+        assert_eq!(hidden[2], vec![true, true, true, true, true, true]);
+    }
+
+    #[test]
+    fn test_hidden_faces_water() {
+        let map = parse_map::<()>(
+            r#"
+{
+"classname" "worldspawn"
+{
+( 0 0 0 ) ( 0 1 0 ) ( 0 0 1 ) wall [ 0 1 0 0 ] [ 0 0 -1 0 ] 0 1 1
+( 0 0 0 ) ( 0 0 1 ) ( 1 0 0 ) wall [ 1 0 0 0 ] [ 0 0 -1 0 ] 0 1 1
+( 0 0 0 ) ( 1 0 0 ) ( 0 1 0 ) wall [ 1 0 0 0 ] [ 0 -1 0 0 ] 0 1 1
+( 64 64 64 ) ( 64 65 64 ) ( 65 64 64 ) wall [ 1 0 0 0 ] [ 0 -1 0 0 ] 0 1 1
+( 64 64 64 ) ( 65 64 64 ) ( 64 64 65 ) wall [ 1 0 0 0 ] [ 0 0 -1 0 ] 0 1 1
+( 64 64 64 ) ( 64 64 65 ) ( 64 65 64 ) wall [ 0 1 0 0 ] [ 0 0 -1 0 ] 0 1 1
+}
+{
+( 0 0 64 ) ( 0 1 64 ) ( 0 0 65 ) *water [ 0 1 0 0 ] [ 0 0 -1 0 ] 0 1 1
+( 0 0 64 ) ( 0 0 65 ) ( 1 0 64 ) *water [ 1 0 0 0 ] [ 0 0 -1 0 ] 0 1 1
+( 0 0 64 ) ( 1 0 64 ) ( 0 1 64 ) *water [ 1 0 0 0 ] [ 0 -1 0 0 ] 0 1 1
+( 64 64 128 ) ( 64 65 128 ) ( 65 64 128 ) *water [ 1 0 0 0 ] [ 0 -1 0 0 ] 0 1 1
+( 64 64 128 ) ( 65 64 128 ) ( 64 64 129 ) *water [ 1 0 0 0 ] [ 0 0 -1 0 ] 0 1 1
+( 64 64 128 ) ( 64 64 129 ) ( 64 65 128 ) *water [ 0 1 0 0 ] [ 0 0 -1 0 ] 0 1 1
+}
+}"#,
+        )
+        .expect("failed to parse")
+        .1;
+        let entity = &map.entities[0];
+
+        let geometries = entity
+            .brushes
+            .iter()
+            .map(|brush| brush.geometry().expect("brush is degenerate"))
+            .collect::<Vec<_>>();
+
+        let hidden = hidden_faces(entity, &geometries, &MapTags::default());
+
+        // The floor under the water stays visible, but the bottom of the water doesn't
+        assert_eq!(hidden[0], vec![false; 6]);
+        assert_eq!(hidden[1], vec![false, false, true, false, false, false]);
+    }
+
+    #[test]
+    fn test_weld_vertices() {
+        let map = parse_map::<()>(BRUSHES).expect("failed to parse").1;
+
+        let mut geometries = map.entities[0]
+            .brushes
+            .iter()
+            .map(|brush| brush.geometry().expect("brush is degenerate"))
+            .collect::<Vec<_>>();
+
+        let offset = DVec3::splat(crate::EPSILON_64 / 2.0);
+        geometries[1].vertices[0] += offset;
+
+        assert_eq!(weld_vertices(&mut geometries), 1);
+        assert!(geometries[1]
+            .vertices
+            .iter()
+            .all(|v| geometries[0].vertices.contains(v) || v.x > 64.0));
+    }
+}
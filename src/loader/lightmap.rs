@@ -0,0 +1,190 @@
+use crate::map_data::Entity as EntityData;
+use bevy::{
+    prelude::{Component, Reflect, ReflectComponent},
+    render::{mesh::MeshVertexAttribute, render_resource::VertexFormat},
+};
+use glam::Vec2;
+
+/// Lightmap UVs of brush meshes, with non-overlapping charts for each face
+pub const ATTRIBUTE_LIGHTMAP_UV: MeshVertexAttribute =
+    MeshVertexAttribute::new("Vertex_Uv_1", 2_135_071_193, VertexFormat::Float32x2);
+
+/// Settings for generating `ATTRIBUTE_LIGHTMAP_UV` on brush meshes.
+/// Each mesh gets its own atlas, sized to fit its faces at the given texel density.
+#[derive(Clone, Copy, Debug)]
+pub struct LightmapSettings {
+    /// The size of a lightmap texel, in .map units.
+    /// Overridden by the `_lightmap_scale` key of worldspawn, as in ericw-tools.
+    pub texel_size: f32,
+    /// Empty texels around each face, so lighting doesn't bleed between faces when filtered
+    pub padding: u32,
+}
+
+impl Default for LightmapSettings {
+    fn default() -> Self {
+        Self {
+            texel_size: 16.0,
+            padding: 1,
+        }
+    }
+}
+
+impl LightmapSettings {
+    /// Applies the lightmap scale of the map's worldspawn
+    pub(crate) fn for_map(self, worldspawn: &EntityData) -> Self {
+        let texel_size = worldspawn
+            .properties
+            .get("_lightmap_scale")
+            .and_then(|scale| scale.trim().parse::<f32>().ok())
+            .filter(|scale| *scale > 0.0)
+            .unwrap_or(self.texel_size);
+
+        Self { texel_size, ..self }
+    }
+}
+
+/// The size of the lightmap atlas of a brush mesh, in texels
+#[derive(Component, Default, Reflect, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct MeshLightmap {
+    pub width: u32,
+    pub height: u32,
+}
+
+/// A face of a brush mesh, as a range of vertices with coordinates on the face's plane
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct LightmapChart {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Packs charts into rows of an atlas, tallest first.
+/// `coords` are in .map units on each face's plane, and the returned UVs are normalized to the atlas.
+pub(crate) fn pack_lightmap(
+    coords: &[Vec2],
+    charts: &[LightmapChart],
+    settings: &LightmapSettings,
+) -> (Vec<Vec2>, MeshLightmap) {
+    let padding = settings.padding as f32;
+
+    // Bounds of each chart in texels, including padding
+    let bounds = charts
+        .iter()
+        .map(|chart| {
+            let chart_coords = &coords[chart.start..chart.end];
+            let min = chart_coords.iter().copied().reduce(Vec2::min).unwrap();
+            let max = chart_coords.iter().copied().reduce(Vec2::max).unwrap();
+
+            let size = ((max - min) / settings.texel_size).ceil() + 2.0 * padding;
+            (min, size)
+        })
+        .collect::<Vec<_>>();
+
+    let area = bounds.iter().map(|(_, size)| size.x * size.y).sum::<f32>();
+    let widest = bounds.iter().map(|(_, size)| size.x).fold(0.0, f32::max);
+    let width = area.sqrt().ceil().max(widest);
+
+    let mut order = (0..charts.len()).collect::<Vec<_>>();
+    order.sort_by(|a, b| bounds[*b].1.y.total_cmp(&bounds[*a].1.y));
+
+    let mut offsets = vec![Vec2::ZERO; charts.len()];
+    let mut cursor = Vec2::ZERO;
+    let mut row_height = 0.0_f32;
+
+    for idx in order {
+        let size = bounds[idx].1;
+
+        if cursor.x + size.x > width {
+            cursor = Vec2::new(0.0, cursor.y + row_height);
+            row_height = 0.0;
+        }
+
+        offsets[idx] = cursor;
+        cursor.x += size.x;
+        row_height = row_height.max(size.y);
+    }
+
+    let atlas_size = Vec2::new(width, cursor.y + row_height).max(Vec2::ONE);
+    let mut uvs = vec![Vec2::ZERO; coords.len()];
+
+    for (chart_idx, chart) in charts.iter().enumerate() {
+        let (min, _) = bounds[chart_idx];
+
+        for vertex in chart.start..chart.end {
+            let texel = offsets[chart_idx] + padding + (coords[vertex] - min) / settings.texel_size;
+            uvs[vertex] = texel / atlas_size;
+        }
+    }
+
+    let lightmap = MeshLightmap {
+        width: atlas_size.x as u32,
+        height: atlas_size.y as u32,
+    };
+
+    (uvs, lightmap)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{pack_lightmap, LightmapChart, LightmapSettings};
+    use glam::Vec2;
+
+    fn square(min: Vec2, size: f32) -> [Vec2; 4] {
+        [
+            min,
+            min + Vec2::new(size, 0.0),
+            min + Vec2::splat(size),
+            min + Vec2::new(0.0, size),
+        ]
+    }
+
+    #[test]
+    fn test_pack_lightmap() {
+        let mut coords = Vec::new();
+        coords.extend(square(Vec2::new(-64.0, 0.0), 64.0));
+        coords.extend(square(Vec2::ZERO, 32.0));
+        coords.extend(square(Vec2::new(100.0, 100.0), 128.0));
+
+        let charts = [0, 4, 8].map(|start| LightmapChart {
+            start,
+            end: start + 4,
+        });
+
+        let settings = LightmapSettings {
+            texel_size: 16.0,
+            padding: 1,
+        };
+
+        let (uvs, lightmap) = pack_lightmap(&coords, &charts, &settings);
+
+        // Charts are 6, 4 and 10 texels wide with padding, and the two smaller ones share a row
+        assert_eq!((lightmap.width, lightmap.height), (13, 16));
+        assert!(uvs
+            .iter()
+            .all(|uv| uv.cmpge(Vec2::ZERO).all() && uv.cmple(Vec2::ONE).all()));
+
+        let texel_bounds = charts
+            .iter()
+            .map(|chart| {
+                let texels = uvs[chart.start..chart.end]
+                    .iter()
+                    .map(|uv| *uv * Vec2::new(13.0, 16.0))
+                    .collect::<Vec<_>>();
+
+                let min = texels.iter().copied().reduce(Vec2::min).unwrap();
+                let max = texels.iter().copied().reduce(Vec2::max).unwrap();
+                (min, max)
+            })
+            .collect::<Vec<_>>();
+
+        // Charts keep their size in texels, and don't overlap
+        assert!((texel_bounds[0].1 - texel_bounds[0].0).abs_diff_eq(Vec2::splat(4.0), 0.001));
+
+        for (i, (min_a, max_a)) in texel_bounds.iter().enumerate() {
+            for (min_b, max_b) in &texel_bounds[(i + 1)..] {
+                let overlaps = min_a.cmplt(*max_b).all() && min_b.cmplt(*max_a).all();
+                assert!(!overlaps);
+            }
+        }
+    }
+}
@@ -1,16 +1,14 @@
-use crate::{map_data::Brush as BrushData, parsing::parse_map};
+use crate::{
+    map_data::{Brush as BrushData, BrushGeometry, Entity as EntityData, Patch as PatchData},
+    parsing::{parse_map_verbose, MapParseError},
+};
 use anyhow::Result as AResult;
 use bevy::{
     asset::{LoadContext, LoadedAsset},
     prelude::*,
-    render::{
-        render_resource::{AddressMode, SamplerDescriptor},
-        texture::CompressedImageFormats,
-    },
+    render::texture::CompressedImageFormats,
+    utils::HashMap as BevyHashMap,
 };
-use bevy_rapier3d::prelude::{Collider, RigidBody};
-use glam::Vec3Swizzles;
-use nom::error::Error as NomError;
 use std::{collections::HashMap, str::Utf8Error, sync::Arc};
 use thiserror::Error;
 
@@ -22,29 +20,65 @@ use asset::*;
 mod asset_provider;
 pub use asset_provider::*;
 
-/// Scale is based on default TrenchBroom obj scale,
-/// which is 64 .map units to 1 obj unit
-const SCALE: f32 = 1.0 / 64.0;
-const TEX_COLLECTIONS_PROP: &str = "_tb_textures";
+mod colliders;
+pub use colliders::*;
+
+mod culling;
+pub use culling::*;
+
+mod lightmap;
+pub use lightmap::*;
+
+mod phong;
+use phong::PhongNormals;
+
+mod asset_loader;
+pub use asset_loader::*;
+
+mod wad_provider;
+pub use wad_provider::*;
+
+mod settings;
+pub use settings::*;
+
+mod shared_textures;
+pub use shared_textures::*;
+
+mod tags;
+pub use tags::*;
+
+mod spawners;
+pub use spawners::*;
+
+/// Worldspawn properties listing texture collections, in order of preference.
+/// Quake-family games use `wad` for WAD packages instead.
+const TEX_COLLECTIONS_PROPS: [&str; 2] = ["_tb_textures", "wad"];
 const EMPTY_TEX: &str = "__TB_empty";
 
+/// Faces of an entity's brushes, merged by texture and chunk (see `MeshBatching`)
+type BatchedMeshes<'b> = HashMap<(&'b str, [i32; 3]), BrushMeshInfo>;
+
+/// Registers `MapAssetLoader`, and the collider system with the `rapier` feature.
+/// Must be added after `DefaultPlugins`, and after inserting any `MapAssetProviderResource` or `MapLoaderSettings`.
 #[derive(Default)]
 pub struct MapPlugin;
 
 impl Plugin for MapPlugin {
     fn build(&self, app: &mut App) {
-        app.register_type::<Brush>().add_system(spawn_map_colliders);
-    }
-}
-
-pub fn spawn_map_colliders(
-    mut commands: Commands,
-    query: Query<(Entity, &Brush), Without<Collider>>,
-) {
-    for (entity, brush) in query.iter() {
-        commands
-            .entity(entity)
-            .insert(Collider::convex_hull(&brush.all_vertices).unwrap());
+        app.init_resource::<MapEntitySpawners>()
+            .init_resource::<MapLoaderSettings>()
+            .init_resource::<SharedMapTextures>()
+            .register_type::<Brush>()
+            .register_type::<MapEntityProperties>()
+            .register_type::<ColliderPolicy>()
+            .register_type::<MapMeshStats>()
+            .register_type::<MeshLightmap>()
+            .init_asset_loader::<MapAssetLoader>()
+            .add_system(configure_shared_map_textures);
+
+        #[cfg(feature = "rapier")]
+        app.add_event::<DegenerateBrushHull>()
+            .add_system(spawn_map_colliders);
     }
 }
 
@@ -56,10 +90,13 @@ pub enum MapError {
     MissingTextureLoadFailed { error: anyhow::Error },
     #[error("not a valid utf-8 string")]
     Utf8Error(#[from] Utf8Error),
-    #[error("failed to parse map")]
-    ParseError,
+    #[error("failed to parse map: {0}")]
+    ParseError(#[from] MapParseError),
     #[error("map does not have a worldspawn entity")]
     MissingWorldspawn,
+    /// Indices are those in the .map file, before layers are removed and entities are folded
+    #[error("brush {brush} of entity {entity} does not enclose a volume")]
+    DegenerateBrush { entity: usize, brush: usize },
 }
 
 pub async fn load_map<'a>(
@@ -67,71 +104,222 @@ pub async fn load_map<'a>(
     load_context: &'a mut LoadContext<'_>,
     supported_compressed_formats: CompressedImageFormats,
     asset_provider: Arc<dyn MapAssetProvider>,
+    spawners: &MapEntitySpawners,
+    settings: &MapLoaderSettings,
+    shared_textures: &SharedMapTextures,
 ) -> AResult<LoadedAsset<Scene>, MapError> {
     let map_text = std::str::from_utf8(bytes)?;
-    let map = parse_map::<NomError<&str>>(map_text)
-        .map_err(|_| MapError::ParseError)?
-        .1;
+    let mut map = parse_map_verbose(map_text)?;
+
+    // Brushes move between entities in these passes, so errors look up where they were parsed
+    let unomitted = map.remove_omitted_layers();
+    let unfolded = map.fold_into_worldspawn(&settings.grouping_classnames);
 
     let worldspawn = map.worldspawn().ok_or(MapError::MissingWorldspawn)?;
-    let texture_collections = worldspawn
-        .properties
-        .get(TEX_COLLECTIONS_PROP)
+    let texture_collections = TEX_COLLECTIONS_PROPS
+        .iter()
+        .find_map(|prop| worldspawn.properties.get(*prop))
         .map(|value| value.split(';').collect::<Vec<_>>());
 
+    let lightmap = settings
+        .lightmap
+        .map(|lightmap| lightmap.for_map(worldspawn));
+
     let mut loaded_textures = HashMap::new();
     let mut loaded_materials = HashMap::new();
 
     let mut world = World::new();
 
     let mut ecs_entities = Vec::new();
+    let mut stats = MapMeshStats::default();
 
     for (entity_idx, entity) in map.entities.iter().enumerate() {
+        let collider = settings.colliders.policy(entity, &settings.tags);
         let mut ecs_brushes = Vec::new();
+        let mut batched_meshes = BatchedMeshes::new();
+
+        let mut geometries = entity
+            .brushes
+            .iter()
+            .enumerate()
+            .map(|(brush_idx, brush)| {
+                brush.geometry().ok_or_else(|| {
+                    let (entity, brush) = unfolded[entity_idx][brush_idx];
+                    let (entity, brush) = unomitted[entity][brush];
+
+                    MapError::DegenerateBrush { entity, brush }
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Other brush entities can move or disappear, so only worldspawn is merged
+        let hidden_faces = if settings.merge_brushes && entity.classname() == Some("worldspawn") {
+            merge_brushes(entity, &mut geometries, &settings.tags, &mut stats)
+        } else {
+            geometries
+                .iter()
+                .map(|geometry| vec![false; geometry.faces.len()])
+                .collect()
+        };
+
+        let phong = PhongNormals::new(entity, &geometries, &hidden_faces, &settings.tags);
 
         for (brush_idx, brush) in entity.brushes.iter().enumerate() {
-            let entity = load_brush(
+            let ecs_brush = load_brush(
                 entity_idx,
                 brush_idx,
+                entity,
                 brush,
+                &geometries[brush_idx],
+                &hidden_faces[brush_idx],
+                phong.as_ref(),
+                lightmap.as_ref(),
+                collider,
+                settings,
+                &mut world,
+                load_context,
+                &asset_provider,
+                supported_compressed_formats,
+                texture_collections.as_ref().map(|c| c as &[&str]),
+                shared_textures,
+                &mut loaded_textures,
+                &mut loaded_materials,
+                &mut batched_meshes,
+            )
+            .await?;
+
+            ecs_brushes.push(ecs_brush);
+        }
+
+        for ((tex_name, chunk), mesh_info) in batched_meshes {
+            let centroid = mesh_info.centroid();
+
+            let ecs_mesh = spawn_brush_mesh(
+                &batch_mesh_label(entity_idx, chunk, tex_name),
+                tex_name,
+                &mesh_info,
+                centroid,
+                Transform::from_translation(utils::map_to_bevy_position(&centroid, settings)),
+                lightmap.as_ref(),
+                settings,
+                &mut world,
+                load_context,
+                &asset_provider,
+                supported_compressed_formats,
+                texture_collections.as_ref().map(|c| c as &[&str]),
+                shared_textures,
+                &mut loaded_textures,
+                &mut loaded_materials,
+            )
+            .await?;
+
+            ecs_brushes.push(ecs_mesh);
+        }
+
+        for (patch_idx, patch) in entity.patches.iter().enumerate() {
+            let ecs_patch = load_patch(
+                entity_idx,
+                patch_idx,
+                patch,
+                settings,
                 &mut world,
                 load_context,
                 &asset_provider,
                 supported_compressed_formats,
                 texture_collections.as_ref().map(|c| c as &[&str]),
+                shared_textures,
                 &mut loaded_textures,
                 &mut loaded_materials,
             )
             .await?;
 
-            ecs_brushes.push(entity);
+            ecs_brushes.extend(ecs_patch);
         }
 
-        let ecs_entity = world
-            .spawn()
-            .insert_bundle(TransformBundle::identity())
-            .push_children(&ecs_brushes)
-            .id();
+        let mut ecs_entity = world.spawn();
+
+        // Brushes are positioned in world space, so only point entities can be moved
+        let transform = if entity.brushes.is_empty() && entity.patches.is_empty() {
+            point_entity_transform(entity, settings)
+        } else {
+            Transform::identity()
+        };
+
+        ecs_entity
+            .insert_bundle(TransformBundle::from(transform))
+            .insert(MapEntityProperties::from(entity))
+            .push_children(&ecs_brushes);
+
+        spawners.spawn(entity, &mut ecs_entity);
 
-        ecs_entities.push(ecs_entity);
+        ecs_entities.push(ecs_entity.id());
     }
 
-    world
-        .spawn()
-        .insert_bundle(TransformBundle::identity())
-        .push_children(&ecs_entities);
+    if settings.merge_brushes {
+        debug!(
+            "merged brushes of {}: welded {} vertices, culled {} faces ({} triangles)",
+            load_context.path().display(),
+            stats.vertices_welded,
+            stats.faces_culled,
+            stats.triangles_culled
+        );
+    }
+
+    // Mirror TrenchBroom layers and groups, with their entities as parents
+    let mut group_entities = HashMap::new();
+
+    for group in map.groups() {
+        let ecs_group = ecs_entities[group.entity];
+
+        group_entities.insert(group.key(), ecs_group);
+        world.entity_mut(ecs_group).insert(Name::new(group.name));
+    }
+
+    let mut root_entities = Vec::new();
+
+    for (entity, ecs_entity) in map.entities.iter().zip(&ecs_entities) {
+        match entity.tb_parent().and_then(|key| group_entities.get(&key)) {
+            Some(ecs_group) if ecs_group != ecs_entity => {
+                world.entity_mut(*ecs_group).push_children(&[*ecs_entity]);
+            }
+            _ => root_entities.push(*ecs_entity),
+        }
+    }
+
+    let mut root = world.spawn();
+
+    root.insert_bundle(TransformBundle::identity())
+        .push_children(&root_entities);
+
+    if settings.merge_brushes {
+        root.insert(stats);
+    }
 
     Ok(LoadedAsset::new(Scene::new(world)))
 }
 
+fn point_entity_transform(entity: &EntityData, settings: &MapLoaderSettings) -> Transform {
+    let mut transform = Transform::identity();
+
+    if let Some(origin) = entity.origin() {
+        transform.translation = utils::map_to_bevy_position(&origin.as_vec3(), settings);
+    }
+
+    if let Some(rotation) = entity.rotation() {
+        transform.rotation = utils::map_to_bevy_rotation(&rotation.as_f32(), settings);
+    }
+
+    transform
+}
+
 async fn load_texture<'a, 'b>(
     tex_name: &'b str,
     load_context: &mut LoadContext<'_>,
     asset_provider: &Arc<dyn MapAssetProvider>,
     supported_compressed_formats: CompressedImageFormats,
     texture_collections: Option<&[&str]>,
-    loaded_textures: &'a mut HashMap<&'b str, Image>,
-) -> AResult<&'a Image, MapError> {
+    loaded_textures: &'a mut HashMap<&'b str, MapTexture>,
+) -> AResult<&'a MapTexture, MapError> {
     if !loaded_textures.contains_key(tex_name) {
         let mut new_tex = if tex_name == EMPTY_TEX {
             asset_provider
@@ -164,11 +352,7 @@ async fn load_texture<'a, 'b>(
             }
         };
 
-        new_tex.sampler_descriptor = SamplerDescriptor {
-            address_mode_u: AddressMode::Repeat,
-            address_mode_v: AddressMode::Repeat,
-            ..default()
-        };
+        new_tex.image.sampler_descriptor = repeating_sampler();
 
         loaded_textures.insert(tex_name, new_tex);
     }
@@ -180,26 +364,42 @@ async fn load_material<'b>(
     tex_name: &'b str,
     load_context: &mut LoadContext<'_>,
     asset_provider: &Arc<dyn MapAssetProvider>,
-    texture: &Image,
+    texture: &MapTexture,
+    shared_textures: &SharedMapTextures,
     loaded_materials: &mut HashMap<&'b str, Handle<StandardMaterial>>,
 ) -> Handle<StandardMaterial> {
     if !loaded_materials.contains_key(tex_name) {
         let custom_material = asset_provider
-            .get_material(tex_name, load_context, texture)
+            .get_material(tex_name, load_context, &texture.image)
             .await;
 
-        let material = custom_material.unwrap_or_else(|| {
-            let tex_handle = load_context
-                .set_labeled_asset(&tex_label(tex_name), LoadedAsset::new(texture.clone()));
-
-            StandardMaterial {
-                base_color_texture: Some(tex_handle),
-                ..default()
+        let material = match (custom_material, &texture.path) {
+            (Some(material), _) => LoadedAsset::new(material),
+            // Reference the shared texture, so the map depends on it
+            (None, Some(path)) => {
+                let tex_handle: Handle<Image> = load_context.get_handle(path.clone());
+                shared_textures.insert(tex_handle.id);
+
+                LoadedAsset::new(StandardMaterial {
+                    base_color_texture: Some(tex_handle),
+                    ..default()
+                })
+                .with_dependency(path.clone())
             }
-        });
+            (None, None) => {
+                let tex_handle = load_context.set_labeled_asset(
+                    &tex_label(tex_name),
+                    LoadedAsset::new(texture.image.clone()),
+                );
+
+                LoadedAsset::new(StandardMaterial {
+                    base_color_texture: Some(tex_handle),
+                    ..default()
+                })
+            }
+        };
 
-        let material_handle =
-            load_context.set_labeled_asset(&mat_label(tex_name), LoadedAsset::new(material));
+        let material_handle = load_context.set_labeled_asset(&mat_label(tex_name), material);
 
         loaded_materials.insert(tex_name, material_handle);
     }
@@ -207,134 +407,317 @@ async fn load_material<'b>(
     loaded_materials[tex_name].clone()
 }
 
+/// The key/value properties of the .map entity a scene entity was spawned from.
+#[derive(Component, Default, Reflect, Clone, Debug)]
+#[reflect(Component)]
+pub struct MapEntityProperties {
+    pub classname: String,
+    pub properties: BevyHashMap<String, String>,
+}
+
+impl MapEntityProperties {
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.properties.get(key).map(|value| value.as_str())
+    }
+}
+
+impl From<&EntityData> for MapEntityProperties {
+    fn from(entity: &EntityData) -> Self {
+        Self {
+            classname: entity.classname().unwrap_or_default().to_string(),
+            properties: entity
+                .properties
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect(),
+        }
+    }
+}
+
+/// A solid brush, for building colliders with any physics engine
 #[derive(Component, Default, Reflect)]
 #[reflect(Component)]
 pub struct Brush {
-    pub(crate) all_vertices: Vec<Vec3>,
+    /// The points of the brush's convex hull, relative to its transform
+    pub all_vertices: Vec<Vec3>,
+    /// How the brush's entity collides
+    pub collider: ColliderPolicy,
 }
 
 #[allow(clippy::too_many_arguments)]
 async fn load_brush<'a, 'b>(
     entity_idx: usize,
     brush_idx: usize,
+    entity: &EntityData,
     brush: &'b BrushData,
+    geometry: &BrushGeometry,
+    hidden_faces: &[bool],
+    phong: Option<&PhongNormals>,
+    lightmap: Option<&LightmapSettings>,
+    collider: ColliderPolicy,
+    settings: &MapLoaderSettings,
     world: &mut World,
     load_context: &mut LoadContext<'_>,
     asset_provider: &Arc<dyn MapAssetProvider>,
     supported_compressed_formats: CompressedImageFormats,
     texture_collections: Option<&[&str]>,
-    loaded_textures: &'a mut HashMap<&'b str, Image>,
+    shared_textures: &SharedMapTextures,
+    loaded_textures: &'a mut HashMap<&'b str, MapTexture>,
     loaded_materials: &'a mut HashMap<&'b str, Handle<StandardMaterial>>,
+    batched_meshes: &mut BatchedMeshes<'b>,
 ) -> AResult<Entity, MapError> {
-    let mut mesh_infos: HashMap<&str, _> = HashMap::new();
-    let mut all_vertices = Vec::new();
-
-    let faces = &brush.faces;
+    let all_vertices = geometry
+        .vertices
+        .iter()
+        .map(|v| v.as_vec3())
+        .collect::<Vec<_>>();
 
-    // Collect all vertices from each brush face
-    for i in 0..faces.len() {
-        let face = &faces[i];
-        let mut face_vertices = Vec::new();
+    let centroid = all_vertices.iter().sum::<Vec3>() / (all_vertices.len() as f32);
+    let chunk = settings.mesh_batching.chunk(centroid);
 
-        for j in 0..faces.len() {
-            if i == j {
-                continue;
-            }
+    let mut mesh_infos: HashMap<&str, BrushMeshInfo> = HashMap::new();
+    let mut is_solid = false;
 
-            for k in (j + 1)..faces.len() {
-                if i == k || j == k {
-                    continue;
-                }
+    for (face_idx, face) in brush.faces.iter().enumerate() {
+        // Faces on duplicate planes, or with no area, have no polygon
+        if geometry.faces[face_idx].is_empty() {
+            continue;
+        }
 
-                if let Some(vertex) = face.intersect_faces(&faces[j], &faces[k]) {
-                    if brush.contains(vertex) {
-                        let vertex = vertex.as_vec3();
+        let face_vertices = geometry.face_vertices(face_idx).collect::<Vec<_>>();
 
-                        face_vertices.push(vertex);
-                        all_vertices.push(vertex);
-                    }
-                }
+        // Tool textures still contribute vertices to the collider
+        match settings.tags.face_behavior(entity, face) {
+            TagBehavior::Render => is_solid = true,
+            TagBehavior::ColliderOnly => {
+                is_solid = true;
+                continue;
             }
+            TagBehavior::Drop => continue,
         }
 
-        let u = face.u.axis.as_vec3();
-        let v = face.v.axis.as_vec3();
+        if hidden_faces[face_idx] {
+            continue;
+        }
 
-        utils::wind(u, v, &mut face_vertices);
+        let entry = match chunk {
+            Some(chunk) => batched_meshes.entry((&face.texture, chunk)).or_default(),
+            None => mesh_infos.entry(&face.texture).or_default(),
+        };
 
-        let entry = mesh_infos
-            .entry(&face.texture)
-            .or_insert_with(BrushMeshInfo::default);
+        let normals = face_vertices
+            .iter()
+            .map(|vertex| match phong {
+                Some(phong) => phong.vertex_normal(face.normal, *vertex).as_vec3(),
+                None => face.normal.normalize().as_vec3(),
+            })
+            .collect::<Vec<_>>();
 
-        entry.push_vertices(face, &face_vertices);
-    }
+        let face_vertices = face_vertices
+            .iter()
+            .map(|v| v.as_vec3())
+            .collect::<Vec<_>>();
 
-    let centroid = all_vertices.iter().sum::<Vec3>() / (all_vertices.len() as f32);
+        entry.push_vertices(face, &face_vertices, &normals);
+    }
 
     let mut ecs_meshes = Vec::new();
 
     for (tex_name, mesh_info) in mesh_infos {
-        let texture = load_texture(
+        let ecs_mesh = spawn_brush_mesh(
+            &mesh_label(entity_idx, brush_idx, tex_name),
             tex_name,
+            &mesh_info,
+            centroid,
+            Transform::identity(),
+            lightmap,
+            settings,
+            world,
             load_context,
             asset_provider,
             supported_compressed_formats,
             texture_collections,
+            shared_textures,
             loaded_textures,
-        )
-        .await?;
-
-        let mesh = mesh_info.to_mesh(centroid, texture.size());
-        let mesh_handle = load_context.set_labeled_asset(
-            &mesh_label(entity_idx, brush_idx, tex_name),
-            LoadedAsset::new(mesh),
-        );
-
-        let material_handle = load_material(
-            tex_name,
-            load_context,
-            asset_provider,
-            texture,
             loaded_materials,
         )
-        .await;
-
-        let ecs_mesh = world
-            .spawn()
-            .insert_bundle(PbrBundle {
-                mesh: mesh_handle,
-                material: material_handle,
-                ..default()
-            })
-            .id();
+        .await?;
 
         ecs_meshes.push(ecs_mesh);
     }
 
     let all_vertices_transformed = all_vertices
         .iter()
-        .map(|v| ((*v - centroid) * SCALE).yzx())
+        .map(|v| utils::map_to_bevy_position(&(*v - centroid), settings))
         .collect::<Vec<_>>();
 
-    let ecs_brush = world
-        .spawn()
+    let mut ecs_brush = world.spawn();
+
+    ecs_brush
         .insert_bundle(TransformBundle::from(Transform::from_translation(
-            (centroid * SCALE).yzx(),
+            utils::map_to_bevy_position(&centroid, settings),
         )))
-        .insert(Brush {
+        .push_children(&ecs_meshes);
+
+    // Brushes made only of dropped faces (e.g. hint brushes) aren't solid
+    if is_solid {
+        ecs_brush.insert(Brush {
             all_vertices: all_vertices_transformed,
+            collider,
+        });
+    }
+
+    Ok(ecs_brush.id())
+}
+
+/// Spawns a mesh of brush faces, with positions relative to `centroid`
+#[allow(clippy::too_many_arguments)]
+async fn spawn_brush_mesh<'a, 'b>(
+    label: &str,
+    tex_name: &'b str,
+    mesh_info: &BrushMeshInfo,
+    centroid: Vec3,
+    transform: Transform,
+    lightmap: Option<&LightmapSettings>,
+    settings: &MapLoaderSettings,
+    world: &mut World,
+    load_context: &mut LoadContext<'_>,
+    asset_provider: &Arc<dyn MapAssetProvider>,
+    supported_compressed_formats: CompressedImageFormats,
+    texture_collections: Option<&[&str]>,
+    shared_textures: &SharedMapTextures,
+    loaded_textures: &'a mut HashMap<&'b str, MapTexture>,
+    loaded_materials: &'a mut HashMap<&'b str, Handle<StandardMaterial>>,
+) -> AResult<Entity, MapError> {
+    let texture = load_texture(
+        tex_name,
+        load_context,
+        asset_provider,
+        supported_compressed_formats,
+        texture_collections,
+        loaded_textures,
+    )
+    .await?;
+
+    let mut mesh = mesh_info.to_mesh(centroid, texture.image.size(), settings);
+
+    let mesh_lightmap = lightmap.map(|lightmap| {
+        let (uvs, mesh_lightmap) = mesh_info.lightmap_uvs(lightmap);
+
+        mesh.insert_attribute(
+            ATTRIBUTE_LIGHTMAP_UV,
+            uvs.iter().map(|uv| uv.to_array()).collect::<Vec<_>>(),
+        );
+
+        mesh_lightmap
+    });
+
+    let mesh_handle = load_context.set_labeled_asset(label, LoadedAsset::new(mesh));
+
+    let material_handle = load_material(
+        tex_name,
+        load_context,
+        asset_provider,
+        texture,
+        shared_textures,
+        loaded_materials,
+    )
+    .await;
+
+    let mut ecs_mesh = world.spawn();
+
+    ecs_mesh.insert_bundle(PbrBundle {
+        mesh: mesh_handle,
+        material: material_handle,
+        transform,
+        ..default()
+    });
+
+    if let Some(mesh_lightmap) = mesh_lightmap {
+        ecs_mesh.insert(mesh_lightmap);
+    }
+
+    Ok(ecs_mesh.id())
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn load_patch<'a, 'b>(
+    entity_idx: usize,
+    patch_idx: usize,
+    patch: &'b PatchData,
+    settings: &MapLoaderSettings,
+    world: &mut World,
+    load_context: &mut LoadContext<'_>,
+    asset_provider: &Arc<dyn MapAssetProvider>,
+    supported_compressed_formats: CompressedImageFormats,
+    texture_collections: Option<&[&str]>,
+    shared_textures: &SharedMapTextures,
+    loaded_textures: &'a mut HashMap<&'b str, MapTexture>,
+    loaded_materials: &'a mut HashMap<&'b str, Handle<StandardMaterial>>,
+) -> AResult<Option<Entity>, MapError> {
+    let tessellated = match patch.tessellate(settings.patch_subdivisions) {
+        Some(tessellated) => tessellated,
+        None => return Ok(None),
+    };
+
+    let mesh_info = PatchMeshInfo::new(&tessellated);
+    let centroid = mesh_info.centroid();
+
+    let texture = load_texture(
+        &patch.texture,
+        load_context,
+        asset_provider,
+        supported_compressed_formats,
+        texture_collections,
+        loaded_textures,
+    )
+    .await?;
+
+    let mesh_handle = load_context.set_labeled_asset(
+        &patch_mesh_label(entity_idx, patch_idx),
+        LoadedAsset::new(mesh_info.to_mesh(centroid, settings)),
+    );
+
+    let material_handle = load_material(
+        &patch.texture,
+        load_context,
+        asset_provider,
+        texture,
+        shared_textures,
+        loaded_materials,
+    )
+    .await;
+
+    let ecs_patch = world
+        .spawn()
+        .insert_bundle(PbrBundle {
+            mesh: mesh_handle,
+            material: material_handle,
+            transform: Transform::from_translation(utils::map_to_bevy_position(
+                &centroid, settings,
+            )),
+            ..default()
         })
-        .insert(RigidBody::Fixed)
-        .push_children(&ecs_meshes)
         .id();
 
-    Ok(ecs_brush)
+    Ok(Some(ecs_patch))
+}
+
+fn patch_mesh_label(entity_idx: usize, patch_idx: usize) -> String {
+    format!("Patch_{}_{}", entity_idx, patch_idx)
 }
 
 fn mesh_label(entity_idx: usize, brush_idx: usize, tex_name: &str) -> String {
     format!("Mesh_{}_{}_{}", entity_idx, brush_idx, tex_name)
 }
 
+fn batch_mesh_label(entity_idx: usize, chunk: [i32; 3], tex_name: &str) -> String {
+    format!(
+        "Batch_{}_{}_{}_{}_{}",
+        entity_idx, chunk[0], chunk[1], chunk[2], tex_name
+    )
+}
+
 fn tex_label(tex_name: &str) -> String {
     format!("Tex_{}", tex_name)
 }
@@ -0,0 +1,149 @@
+use super::{MapTags, TagBehavior};
+use crate::map_data::{BrushGeometry, Entity as EntityData};
+use glam::DVec3;
+use std::collections::HashMap;
+
+/// The default `_phong_angle` of ericw-tools, in degrees
+const DEFAULT_PHONG_ANGLE: f64 = 89.0;
+
+/// Smoothed vertex normals for the brushes of an entity with `_phong 1`, as in ericw-tools.
+/// A face's vertex normal averages the normals of the faces sharing the vertex,
+/// if they are within `_phong_angle` degrees of the face's normal.
+pub(crate) struct PhongNormals {
+    min_dot: f64,
+    face_normals: HashMap<[i64; 3], Vec<DVec3>>,
+}
+
+impl PhongNormals {
+    /// Collects the rendered faces of an entity, or returns `None` if it isn't smoothed
+    pub fn new(
+        entity: &EntityData,
+        geometries: &[BrushGeometry],
+        hidden_faces: &[Vec<bool>],
+        tags: &MapTags,
+    ) -> Option<Self> {
+        let angle = entity
+            .properties
+            .get("_phong_angle")
+            .and_then(|angle| angle.trim().parse::<f64>().ok());
+
+        // Setting an angle implies `_phong 1`
+        let enabled = entity.properties.get("_phong").map(|phong| phong.trim()) == Some("1");
+
+        if !enabled && angle.is_none() {
+            return None;
+        }
+
+        let angle = angle.unwrap_or(DEFAULT_PHONG_ANGLE);
+
+        let mut face_normals: HashMap<_, Vec<DVec3>> = HashMap::new();
+
+        for ((brush, geometry), hidden) in entity.brushes.iter().zip(geometries).zip(hidden_faces) {
+            for (face_idx, face) in brush.faces.iter().enumerate() {
+                if hidden[face_idx] || tags.face_behavior(entity, face) != TagBehavior::Render {
+                    continue;
+                }
+
+                let normal = face.normal.normalize();
+
+                for vertex in geometry.face_vertices(face_idx) {
+                    face_normals.entry(key(vertex)).or_default().push(normal);
+                }
+            }
+        }
+
+        Some(Self {
+            min_dot: angle.to_radians().cos(),
+            face_normals,
+        })
+    }
+
+    pub fn vertex_normal(&self, face_normal: DVec3, vertex: DVec3) -> DVec3 {
+        let face_normal = face_normal.normalize();
+
+        let normal = self
+            .face_normals
+            .get(&key(vertex))
+            .into_iter()
+            .flatten()
+            .filter(|normal| normal.dot(face_normal) >= self.min_dot - crate::EPSILON_64)
+            .sum::<DVec3>();
+
+        if normal.length_squared() > crate::EPSILON_64 {
+            normal.normalize()
+        } else {
+            face_normal
+        }
+    }
+}
+
+/// Vertices are matched to the nearest `EPSILON`, so adjacent brushes share normals
+fn key(vertex: DVec3) -> [i64; 3] {
+    (vertex / crate::EPSILON_64)
+        .round()
+        .to_array()
+        .map(|c| c as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PhongNormals;
+    use crate::{loader::MapTags, map_data::Entity as EntityData, test_utils::get_cylinder};
+    use glam::DVec3;
+    use std::collections::HashMap;
+
+    fn cylinder_entity(properties: &[(&str, &str)]) -> EntityData {
+        EntityData {
+            properties: properties
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<HashMap<_, _>>(),
+            brushes: vec![get_cylinder(8)],
+            patches: Vec::new(),
+        }
+    }
+
+    fn phong_normals(entity: &EntityData) -> Option<PhongNormals> {
+        let geometries = vec![entity.brushes[0].geometry().unwrap()];
+        let hidden = vec![vec![false; entity.brushes[0].faces.len()]];
+
+        PhongNormals::new(entity, &geometries, &hidden, &MapTags::default())
+    }
+
+    #[test]
+    fn test_phong_normals() {
+        assert!(phong_normals(&cylinder_entity(&[])).is_none());
+
+        let entity = cylinder_entity(&[("_phong", "1")]);
+        let brush = &entity.brushes[0];
+        let phong = phong_normals(&entity).expect("phong is disabled");
+
+        let geometry = brush.geometry().unwrap();
+        let side = &brush.faces[0];
+        let cap = &brush.faces[8];
+
+        for vertex in geometry.face_vertices(0) {
+            // Sides are smoothed with the neighboring sides, but not the caps
+            let normal = phong.vertex_normal(side.normal, vertex);
+            let radial = DVec3::new(vertex.x, vertex.y, 0.0).normalize();
+
+            assert!(normal.abs_diff_eq(radial, crate::EPSILON_64));
+        }
+
+        for vertex in geometry.face_vertices(8) {
+            assert!(phong
+                .vertex_normal(cap.normal, vertex)
+                .abs_diff_eq(DVec3::Z, crate::EPSILON_64));
+        }
+
+        // Sides are 45 degrees apart
+        let entity = cylinder_entity(&[("_phong_angle", "30")]);
+        let phong = phong_normals(&entity).expect("phong is disabled");
+
+        for vertex in geometry.face_vertices(0) {
+            assert!(phong
+                .vertex_normal(side.normal, vertex)
+                .abs_diff_eq(side.normal.normalize(), crate::EPSILON_64));
+        }
+    }
+}
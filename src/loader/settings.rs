@@ -0,0 +1,117 @@
+use super::{LightmapSettings, MapColliders, MapTags};
+use glam::Vec3;
+
+/// Settings which affect how maps are converted to Bevy scenes.
+/// `MapPlugin` adds this as a resource, which map loaders can read when they are created.
+#[derive(Clone, Debug)]
+pub struct MapLoaderSettings {
+    /// The number of steps each 3x3 section of a patch is divided into, in each direction
+    pub patch_subdivisions: usize,
+    /// The number of .map units in one Bevy unit.
+    /// Defaults to 64, the default TrenchBroom obj scale.
+    pub units_per_meter: f32,
+    /// The axis which points up in the map (Z for Quake maps)
+    pub up_axis: UpAxis,
+    /// The handedness of the map's coordinate system (right-handed for Quake maps)
+    pub handedness: Handedness,
+    /// Decides which faces are rendered, e.g. to hide tool textures
+    pub tags: MapTags,
+    /// Decides which colliders and rigid bodies each entity gets
+    pub colliders: MapColliders,
+    /// Welds the vertices of worldspawn brushes, and removes faces completely covered by other brushes.
+    /// Statistics are added to the scene as `MapMeshStats`.
+    pub merge_brushes: bool,
+    /// How brush faces are grouped into meshes
+    pub mesh_batching: MeshBatching,
+    /// Classnames of entities which only organize brushes in the editor.
+    /// Their brushes are loaded as part of their TrenchBroom layer or group, or worldspawn otherwise,
+    /// and the entities themselves are skipped.
+    /// TrenchBroom layers and groups are kept, as parents of the entities in them.
+    pub grouping_classnames: Vec<String>,
+    /// Generates a second set of UVs on brush meshes (`ATTRIBUTE_LIGHTMAP_UV`) for baked lighting,
+    /// and adds a `MeshLightmap` with the size of the atlas to each mesh entity
+    pub lightmap: Option<LightmapSettings>,
+}
+
+impl Default for MapLoaderSettings {
+    fn default() -> Self {
+        Self {
+            patch_subdivisions: 8,
+            units_per_meter: 64.0,
+            up_axis: UpAxis::Z,
+            handedness: Handedness::Right,
+            tags: MapTags::default(),
+            colliders: MapColliders::default(),
+            merge_brushes: false,
+            mesh_batching: MeshBatching::Brush,
+            grouping_classnames: vec!["func_group".to_string(), "func_detail".to_string()],
+            lightmap: None,
+        }
+    }
+}
+
+/// How brush faces are grouped into meshes.
+/// Batched meshes are children of the entity rather than its brushes, which keep their colliders.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MeshBatching {
+    /// A mesh per brush and texture
+    Brush,
+    /// A mesh per entity and texture
+    Entity,
+    /// A mesh per entity, texture and cubic cell of the given size (in .map units).
+    /// Brushes are assigned to the cell containing their centroid.
+    Chunked(f32),
+}
+
+impl MeshBatching {
+    /// The cell containing a brush, if brushes are batched
+    pub(crate) fn chunk(self, centroid: Vec3) -> Option<[i32; 3]> {
+        match self {
+            MeshBatching::Brush => None,
+            MeshBatching::Entity => Some([0; 3]),
+            MeshBatching::Chunked(size) => Some((centroid / size).floor().as_ivec3().to_array()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum UpAxis {
+    X,
+    Y,
+    Z,
+}
+
+impl UpAxis {
+    /// The indices of the up axis and the two axes following it in cyclic order
+    pub(crate) fn cyclic_indices(self) -> [usize; 3] {
+        match self {
+            UpAxis::X => [0, 1, 2],
+            UpAxis::Y => [1, 2, 0],
+            UpAxis::Z => [2, 0, 1],
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Handedness {
+    Right,
+    Left,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MeshBatching;
+    use glam::Vec3;
+
+    #[test]
+    fn test_mesh_batching_chunk() {
+        let centroid = Vec3::new(100.0, -20.0, 512.0);
+
+        assert_eq!(MeshBatching::Brush.chunk(centroid), None);
+        assert_eq!(MeshBatching::Entity.chunk(centroid), Some([0, 0, 0]));
+        assert_eq!(
+            MeshBatching::Chunked(512.0).chunk(centroid),
+            Some([0, -1, 1])
+        );
+    }
+}
@@ -0,0 +1,77 @@
+use bevy::{
+    asset::HandleId,
+    prelude::{AssetEvent, Assets, EventReader, Image, Res, ResMut},
+    render::render_resource::{AddressMode, SamplerDescriptor},
+};
+use std::{
+    collections::HashSet,
+    sync::{Arc, RwLock},
+};
+
+/// Textures shared between maps as standalone assets, rather than embedded in each map.
+/// Their samplers are set to repeat whenever they are (re)loaded, as the `AssetServer` does not do so.
+///
+/// Clones share the same set, so map loaders can register textures from other threads.
+#[derive(Clone, Default)]
+pub struct SharedMapTextures {
+    inner: Arc<RwLock<SharedMapTexturesInner>>,
+}
+
+#[derive(Default)]
+struct SharedMapTexturesInner {
+    ids: HashSet<HandleId>,
+    /// Newly registered textures, which may have been loaded before they were registered
+    pending: Vec<HandleId>,
+}
+
+impl SharedMapTextures {
+    pub fn insert(&self, id: HandleId) {
+        let mut inner = self.inner.write().unwrap();
+
+        if inner.ids.insert(id) {
+            inner.pending.push(id);
+        }
+    }
+
+    pub fn contains(&self, id: HandleId) -> bool {
+        self.inner.read().unwrap().ids.contains(&id)
+    }
+}
+
+pub(crate) fn repeating_sampler() -> SamplerDescriptor<'static> {
+    SamplerDescriptor {
+        address_mode_u: AddressMode::Repeat,
+        address_mode_v: AddressMode::Repeat,
+        ..Default::default()
+    }
+}
+
+pub fn configure_shared_map_textures(
+    shared_textures: Res<SharedMapTextures>,
+    mut events: EventReader<AssetEvent<Image>>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let mut ids = std::mem::take(&mut shared_textures.inner.write().unwrap().pending);
+
+    for event in events.iter() {
+        if let AssetEvent::Created { handle } | AssetEvent::Modified { handle } = event {
+            if shared_textures.contains(handle.id) {
+                ids.push(handle.id);
+            }
+        }
+    }
+
+    for id in ids {
+        // Mutating the image sends another `Modified` event, so only do it once
+        let needs_update = images.get(id).is_some_and(|image| {
+            image.sampler_descriptor.address_mode_u != AddressMode::Repeat
+                || image.sampler_descriptor.address_mode_v != AddressMode::Repeat
+        });
+
+        if needs_update {
+            if let Some(image) = images.get_mut(id) {
+                image.sampler_descriptor = repeating_sampler();
+            }
+        }
+    }
+}
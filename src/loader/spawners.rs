@@ -0,0 +1,115 @@
+use crate::map_data::Entity as EntityData;
+use bevy::{ecs::world::EntityMut, prelude::App};
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+pub type MapEntitySpawner = dyn Fn(&EntityData, &mut EntityMut) + Send + Sync;
+
+/// Registry of handlers, keyed by classname, which are run on every matching entity when a map is loaded.
+/// Handlers receive the parsed entity and the scene entity spawned for it, and can insert any components.
+/// Components inserted this way must be registered (and reflect `Component`) for the scene to spawn.
+///
+/// Clones share the same registry, so handlers registered after a loader is created are still used.
+#[derive(Clone, Default)]
+pub struct MapEntitySpawners {
+    spawners: Arc<RwLock<HashMap<String, Arc<MapEntitySpawner>>>>,
+}
+
+impl MapEntitySpawners {
+    /// Registers a handler for `classname`, replacing any existing one
+    pub fn register<F>(&self, classname: impl Into<String>, spawner: F)
+    where
+        F: Fn(&EntityData, &mut EntityMut) + Send + Sync + 'static,
+    {
+        self.spawners
+            .write()
+            .unwrap()
+            .insert(classname.into(), Arc::new(spawner));
+    }
+
+    pub fn contains(&self, classname: &str) -> bool {
+        self.spawners.read().unwrap().contains_key(classname)
+    }
+
+    /// Runs the handler registered for the entity's classname, if there is one
+    pub fn spawn(&self, entity: &EntityData, ecs_entity: &mut EntityMut) {
+        let spawner = match entity.classname() {
+            Some(classname) => self.spawners.read().unwrap().get(classname).cloned(),
+            None => None,
+        };
+
+        if let Some(spawner) = spawner {
+            spawner(entity, ecs_entity);
+        }
+    }
+}
+
+pub trait MapEntitySpawnerAppExt {
+    /// Registers a handler to run on map entities with the given `classname` as they are loaded
+    fn register_map_entity<F>(&mut self, classname: impl Into<String>, spawner: F) -> &mut Self
+    where
+        F: Fn(&EntityData, &mut EntityMut) + Send + Sync + 'static;
+}
+
+impl MapEntitySpawnerAppExt for App {
+    fn register_map_entity<F>(&mut self, classname: impl Into<String>, spawner: F) -> &mut Self
+    where
+        F: Fn(&EntityData, &mut EntityMut) + Send + Sync + 'static,
+    {
+        self.world
+            .get_resource_or_insert_with(MapEntitySpawners::default)
+            .register(classname, spawner);
+
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MapEntitySpawners;
+    use crate::map_data::Entity as EntityData;
+    use bevy::prelude::{Component, World};
+    use std::collections::HashMap;
+
+    #[derive(Component)]
+    struct Light(f32);
+
+    fn entity(classname: &str) -> EntityData {
+        let mut properties = HashMap::new();
+        properties.insert("classname".to_string(), classname.to_string());
+        properties.insert("light".to_string(), "300".to_string());
+
+        EntityData {
+            properties,
+            brushes: Vec::new(),
+            patches: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_spawn() {
+        let spawners = MapEntitySpawners::default();
+
+        spawners.clone().register("light", |entity, ecs_entity| {
+            let brightness = entity.properties["light"].parse().unwrap();
+            ecs_entity.insert(Light(brightness));
+        });
+
+        assert!(spawners.contains("light"));
+
+        let mut world = World::new();
+
+        let mut light = world.spawn();
+        spawners.spawn(&entity("light"), &mut light);
+        let light = light.id();
+
+        let mut other = world.spawn();
+        spawners.spawn(&entity("info_player_start"), &mut other);
+        let other = other.id();
+
+        assert_eq!(world.get::<Light>(light).map(|l| l.0), Some(300.0));
+        assert!(world.get::<Light>(other).is_none());
+    }
+}
@@ -0,0 +1,433 @@
+use crate::{
+    game_config::{GameConfig, Tag, TagAttribute, TagMatchType},
+    map_data::{BrushFace, Entity as EntityData},
+};
+
+/// How faces matching a tag are built
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TagBehavior {
+    /// Rendered, with a collider
+    Render,
+    /// Not rendered, but still solid (e.g. `clip`, `skip` and triggers)
+    ColliderOnly,
+    /// Neither rendered nor solid (e.g. `hint`)
+    Drop,
+}
+
+#[derive(Clone, Debug)]
+struct TagRule {
+    name: String,
+    matcher: TagMatcher,
+    behavior: TagBehavior,
+    /// From the `transparent` attribute of a game config tag
+    translucent: bool,
+}
+
+#[derive(Clone, Debug)]
+enum TagMatcher {
+    ClassName(String),
+    Texture(String),
+    ContentFlags(i32),
+    SurfaceFlags(i32),
+}
+
+impl TagMatcher {
+    fn matches_entity(&self, entity: &EntityData) -> bool {
+        match self {
+            TagMatcher::ClassName(pattern) => entity
+                .classname()
+                .is_some_and(|classname| matches_pattern(pattern, classname)),
+            _ => false,
+        }
+    }
+
+    fn matches_face(&self, face: &BrushFace) -> bool {
+        match self {
+            TagMatcher::ClassName(_) => false,
+            // Texture collections are not part of the name, as in TrenchBroom
+            TagMatcher::Texture(pattern) => {
+                let name = face.texture.rsplit('/').next().unwrap_or_default();
+                matches_pattern(pattern, name)
+            }
+            TagMatcher::ContentFlags(flags) => face
+                .surface
+                .is_some_and(|surface| surface.content_flags & flags != 0),
+            TagMatcher::SurfaceFlags(flags) => face
+                .surface
+                .is_some_and(|surface| surface.surface_flags & flags != 0),
+        }
+    }
+}
+
+/// Decides which brushes and faces get meshes and colliders, based on TrenchBroom's smart tags.
+/// The first matching tag wins, and brush tags (matched by classname) take priority over face tags,
+/// unless they are rendered, so tool textures are still hidden on those brushes.
+///
+/// By default, common Quake tool textures and `trigger*` entities are not rendered.
+#[derive(Clone, Debug)]
+pub struct MapTags {
+    brush: Vec<TagRule>,
+    brush_face: Vec<TagRule>,
+}
+
+impl Default for MapTags {
+    fn default() -> Self {
+        let mut tags = MapTags::empty();
+
+        tags.add_brush_tag(
+            "Trigger",
+            TagMatchType::ClassName,
+            "trigger*",
+            TagBehavior::ColliderOnly,
+        )
+        .add_face_tag(
+            "Clip",
+            TagMatchType::Texture,
+            "*clip",
+            TagBehavior::ColliderOnly,
+        )
+        .add_face_tag(
+            "Skip",
+            TagMatchType::Texture,
+            "skip",
+            TagBehavior::ColliderOnly,
+        )
+        .add_face_tag(
+            "Trigger",
+            TagMatchType::Texture,
+            "trigger",
+            TagBehavior::ColliderOnly,
+        )
+        .add_face_tag(
+            "Empty",
+            TagMatchType::Texture,
+            super::EMPTY_TEX,
+            TagBehavior::ColliderOnly,
+        )
+        .add_face_tag("Hint", TagMatchType::Texture, "hint*", TagBehavior::Drop);
+
+        tags
+    }
+}
+
+impl MapTags {
+    /// No tags, so everything is rendered
+    pub fn empty() -> Self {
+        Self {
+            brush: Vec::new(),
+            brush_face: Vec::new(),
+        }
+    }
+
+    /// Uses the tags of a game config, where `transparent` tags are not rendered.
+    /// Tags matching unknown flags, or surface parameters, are ignored.
+    pub fn from_config(config: &GameConfig) -> Self {
+        let rule = |tag: &Tag| {
+            let pattern = tag.pattern.clone().unwrap_or_default();
+
+            let flags = || {
+                tag.flags.iter().flatten().try_fold(0, |acc, name| {
+                    match tag.r#match {
+                        TagMatchType::ContentFlag => config.face_attribs.content_flag(name),
+                        _ => config.face_attribs.surface_flag(name),
+                    }
+                    .map(|bit| acc | bit)
+                })
+            };
+
+            let matcher = match tag.r#match {
+                TagMatchType::ClassName => TagMatcher::ClassName(pattern),
+                TagMatchType::Texture => TagMatcher::Texture(pattern),
+                TagMatchType::ContentFlag => TagMatcher::ContentFlags(flags()?),
+                TagMatchType::SurfaceFlag => TagMatcher::SurfaceFlags(flags()?),
+                TagMatchType::SurfaceParam => return None,
+            };
+
+            let transparent = tag
+                .attribs
+                .iter()
+                .flatten()
+                .any(|attrib| matches!(attrib, TagAttribute::Transparent));
+
+            Some(TagRule {
+                name: tag.name.clone(),
+                matcher,
+                behavior: if transparent {
+                    TagBehavior::ColliderOnly
+                } else {
+                    TagBehavior::Render
+                },
+                translucent: transparent,
+            })
+        };
+
+        Self {
+            brush: config.tags.brush.iter().filter_map(rule).collect(),
+            brush_face: config.tags.brush_face.iter().filter_map(rule).collect(),
+        }
+    }
+
+    /// Adds a tag matched against the classname of a brush's entity
+    pub fn add_brush_tag(
+        &mut self,
+        name: impl Into<String>,
+        match_type: TagMatchType,
+        pattern: impl Into<String>,
+        behavior: TagBehavior,
+    ) -> &mut Self {
+        if let Some(matcher) = Self::matcher(match_type, pattern.into()) {
+            self.brush.push(TagRule {
+                name: name.into(),
+                matcher,
+                behavior,
+                translucent: false,
+            });
+        }
+
+        self
+    }
+
+    /// Adds a tag matched against a face's texture name
+    pub fn add_face_tag(
+        &mut self,
+        name: impl Into<String>,
+        match_type: TagMatchType,
+        pattern: impl Into<String>,
+        behavior: TagBehavior,
+    ) -> &mut Self {
+        if let Some(matcher) = Self::matcher(match_type, pattern.into()) {
+            self.brush_face.push(TagRule {
+                name: name.into(),
+                matcher,
+                behavior,
+                translucent: false,
+            });
+        }
+
+        self
+    }
+
+    /// Changes the behavior of every tag called `name`, e.g. to drop `Skip` faces instead
+    pub fn set_behavior(&mut self, name: &str, behavior: TagBehavior) -> &mut Self {
+        for rule in self.brush.iter_mut().chain(self.brush_face.iter_mut()) {
+            if rule.name == name {
+                rule.behavior = behavior;
+            }
+        }
+
+        self
+    }
+
+    /// Flag tags need a game config to resolve flag names
+    fn matcher(match_type: TagMatchType, pattern: String) -> Option<TagMatcher> {
+        match match_type {
+            TagMatchType::ClassName => Some(TagMatcher::ClassName(pattern)),
+            TagMatchType::Texture => Some(TagMatcher::Texture(pattern)),
+            _ => None,
+        }
+    }
+
+    /// The name of the first brush tag matching the entity's classname
+    pub fn brush_tag(&self, entity: &EntityData) -> Option<&str> {
+        self.brush
+            .iter()
+            .find(|rule| rule.matcher.matches_entity(entity))
+            .map(|rule| rule.name.as_str())
+    }
+
+    pub fn face_behavior(&self, entity: &EntityData, face: &BrushFace) -> TagBehavior {
+        self.rule(entity, face)
+            .map_or(TagBehavior::Render, |rule| rule.behavior)
+    }
+
+    /// Whether a face can be seen through, so it doesn't hide faces behind it:
+    /// liquids (`*water`), alpha-tested textures (`{grate`), and faces matching `transparent` tags
+    pub fn is_translucent(&self, entity: &EntityData, face: &BrushFace) -> bool {
+        let name = face.texture.rsplit('/').next().unwrap_or_default();
+
+        name.starts_with('*')
+            || name.starts_with('{')
+            || self.rule(entity, face).is_some_and(|rule| rule.translucent)
+    }
+
+    fn rule(&self, entity: &EntityData, face: &BrushFace) -> Option<&TagRule> {
+        self.brush
+            .iter()
+            .find(|rule| rule.matcher.matches_entity(entity))
+            .filter(|rule| rule.behavior != TagBehavior::Render)
+            .or_else(|| {
+                self.brush_face
+                    .iter()
+                    .find(|rule| rule.matcher.matches_face(face))
+            })
+    }
+}
+
+/// Matches TrenchBroom tag patterns, where `*` matches any characters and `?` any one character
+fn matches_pattern(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.to_ascii_lowercase().chars().collect::<Vec<_>>();
+    let text = text.to_ascii_lowercase().chars().collect::<Vec<_>>();
+
+    // Whether `pattern[..i]` matches `text[..j]`
+    let mut matches = vec![vec![false; text.len() + 1]; pattern.len() + 1];
+    matches[0][0] = true;
+
+    for i in 1..=pattern.len() {
+        for j in 0..=text.len() {
+            matches[i][j] = match pattern[i - 1] {
+                '*' => matches[i - 1][j] || (j > 0 && matches[i][j - 1]),
+                '?' => j > 0 && matches[i - 1][j - 1],
+                c => j > 0 && matches[i - 1][j - 1] && text[j - 1] == c,
+            };
+        }
+    }
+
+    matches[pattern.len()][text.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{matches_pattern, MapTags, TagBehavior};
+    use crate::{
+        game_config::GameConfig,
+        map_data::{Entity as EntityData, SurfaceAttributes},
+        test_utils::get_brush,
+    };
+    use std::collections::HashMap;
+
+    fn entity(classname: &str) -> EntityData {
+        EntityData {
+            properties: HashMap::from([("classname".to_string(), classname.to_string())]),
+            brushes: Vec::new(),
+            patches: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_matches_pattern() {
+        assert!(matches_pattern("trigger*", "trigger_multiple"));
+        assert!(matches_pattern("*clip", "map/PLAYERCLIP"));
+        assert!(matches_pattern("sk?p", "skip"));
+        assert!(!matches_pattern("clip", "clipper"));
+        assert!(!matches_pattern("?", ""));
+    }
+
+    #[test]
+    fn test_face_behavior() {
+        let tags = MapTags::default();
+        let mut face = get_brush().faces.remove(0);
+
+        assert_eq!(
+            tags.face_behavior(&entity("worldspawn"), &face),
+            TagBehavior::Render
+        );
+        assert_eq!(
+            tags.face_behavior(&entity("trigger_once"), &face),
+            TagBehavior::ColliderOnly
+        );
+
+        face.texture = "tools/clip".to_string();
+        assert_eq!(
+            tags.face_behavior(&entity("worldspawn"), &face),
+            TagBehavior::ColliderOnly
+        );
+
+        face.texture = "hint".to_string();
+        assert_eq!(
+            tags.face_behavior(&entity("worldspawn"), &face),
+            TagBehavior::Drop
+        );
+        assert!(!tags.is_translucent(&entity("worldspawn"), &face));
+
+        face.texture = "liquids/*water1".to_string();
+        assert_eq!(
+            tags.face_behavior(&entity("worldspawn"), &face),
+            TagBehavior::Render
+        );
+        assert!(tags.is_translucent(&entity("worldspawn"), &face));
+    }
+
+    #[test]
+    fn test_from_config() {
+        let tags = serde_json::from_str(
+            r#"{
+                "brush": [],
+                "brushface": [
+                    { "name": "Skip", "attribs": [ "transparent" ], "match": "texture", "pattern": "skip" },
+                    { "name": "Detail", "attribs": [], "match": "contentflag", "flags": [ "detail" ] },
+                    { "name": "Nodraw", "attribs": [ "transparent" ], "match": "surfaceflag", "flags": [ "nodraw" ] }
+                ]
+            }"#,
+        )
+        .unwrap();
+        let face_attribs = serde_json::from_str(
+            r#"{
+                "surfaceflags": [ { "name": "light" }, { "name": "nodraw" } ],
+                "contentflags": [ { "name": "solid" } ]
+            }"#,
+        )
+        .unwrap();
+        let config = GameConfig {
+            tags,
+            face_attribs,
+            ..Default::default()
+        };
+
+        let mut tags = MapTags::from_config(&config);
+        let mut face = get_brush().faces.remove(0);
+        let worldspawn = entity("worldspawn");
+
+        // Unknown flags are ignored, and clip isn't in this config
+        face.texture = "clip".to_string();
+        face.surface = Some(SurfaceAttributes {
+            content_flags: 1,
+            surface_flags: 0b10,
+            surface_value: 0.0,
+        });
+        assert_eq!(
+            tags.face_behavior(&worldspawn, &face),
+            TagBehavior::ColliderOnly
+        );
+
+        face.surface = None;
+        assert_eq!(tags.face_behavior(&worldspawn, &face), TagBehavior::Render);
+
+        face.texture = "skip".to_string();
+        tags.set_behavior("Skip", TagBehavior::Drop);
+        assert_eq!(tags.face_behavior(&worldspawn, &face), TagBehavior::Drop);
+        assert!(tags.is_translucent(&worldspawn, &face));
+    }
+
+    #[test]
+    fn test_rendered_brush_tag() {
+        let tags = serde_json::from_str(
+            r#"{
+                "brush": [
+                    { "name": "Detail", "attribs": [], "match": "classname", "pattern": "func_detail*" }
+                ],
+                "brushface": [
+                    { "name": "Clip", "attribs": [ "transparent" ], "match": "texture", "pattern": "clip" }
+                ]
+            }"#,
+        )
+        .unwrap();
+        let config = GameConfig {
+            tags,
+            ..Default::default()
+        };
+
+        let tags = MapTags::from_config(&config);
+        let mut face = get_brush().faces.remove(0);
+        let detail = entity("func_detail");
+
+        assert_eq!(tags.brush_tag(&detail), Some("Detail"));
+        assert_eq!(tags.face_behavior(&detail, &face), TagBehavior::Render);
+
+        // Rendered brush tags don't hide face tags
+        face.texture = "clip".to_string();
+        assert_eq!(
+            tags.face_behavior(&detail, &face),
+            TagBehavior::ColliderOnly
+        );
+    }
+}
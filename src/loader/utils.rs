@@ -1,68 +1,129 @@
-use glam::{Vec2, Vec3, Vec4};
-use std::cmp::Ordering;
+use super::{Handedness, MapLoaderSettings};
+use glam::{Mat3, Quat, Vec3, Vec4};
 
-pub fn map_to_bevy_space3(v: &Vec3) -> [f32; 3] {
-    [v.y, v.z, v.x]
+/// Converts a direction from .map space to Bevy space (Y up, right-handed), without scaling it
+pub fn map_to_bevy_space3(v: &Vec3, settings: &MapLoaderSettings) -> Vec3 {
+    // For Z up, this maps X to Z and Y to X
+    let [up, forward, right] = settings.up_axis.cyclic_indices();
+    let mut converted = Vec3::new(v[right], v[up], v[forward]);
+
+    if settings.handedness == Handedness::Left {
+        converted.z = -converted.z;
+    }
+
+    converted
 }
 
-pub fn map_to_bevy_space4(v: &Vec4) -> [f32; 4] {
-    [v.y, v.z, v.x, v.w]
+/// Converts a tangent from .map space, flipping its handedness (`w`) if the map is mirrored
+pub fn map_to_bevy_space4(v: &Vec4, settings: &MapLoaderSettings) -> Vec4 {
+    let w = match settings.handedness {
+        Handedness::Right => v.w,
+        Handedness::Left => -v.w,
+    };
+
+    map_to_bevy_space3(&v.truncate(), settings).extend(w)
 }
 
-// Projects `point` onto a plane with axes `u` and `v`
-fn project(u: Vec3, v: Vec3, point: Vec3) -> Vec2 {
-    Vec2::new(u.dot(point), v.dot(point))
+/// Converts a position from .map space, scaling it to Bevy units
+pub fn map_to_bevy_position(v: &Vec3, settings: &MapLoaderSettings) -> Vec3 {
+    map_to_bevy_space3(&(*v / settings.units_per_meter), settings)
 }
 
-// TODO: Testing possible? Order is not generally guaranteed
-/// Sorts an arbitrary number of vertices in clockwise order
-/// assuming they form a convex shape (https://stackoverflow.com/a/6989383)
-pub fn wind(u: Vec3, v: Vec3, vertices: &mut Vec<Vec3>) {
-    let center = vertices.iter().sum::<Vec3>() / (vertices.len() as f32);
-    let center_proj = project(u, v, center);
+/// Converts an orientation from .map space (facing +X with +Z up when unrotated),
+/// such that `Transform::forward` points in the direction the entity faces
+pub fn map_to_bevy_rotation(q: &Quat, settings: &MapLoaderSettings) -> Quat {
+    // Rotation axes are pseudovectors, so they flip again when mirrored
+    let axis = match settings.handedness {
+        Handedness::Right => map_to_bevy_space3(&q.xyz(), settings),
+        Handedness::Left => -map_to_bevy_space3(&q.xyz(), settings),
+    };
 
-    vertices.sort_by(|a, b| {
-        let a_proj = project(u, v, *a);
-        let b_proj = project(u, v, *b);
+    // Rotates Bevy's forward (-Z) and up (+Y) onto the converted .map ones
+    let forward = map_to_bevy_space3(&Vec3::X, settings);
+    let up = map_to_bevy_space3(&Vec3::Z, settings);
+    let base = Quat::from_mat3(&Mat3::from_cols(forward.cross(up), up, -forward));
 
-        let a_off = a_proj - center_proj;
-        let b_off = b_proj - center_proj;
+    Quat::from_xyzw(axis.x, axis.y, axis.z, q.w) * base
+}
 
-        // Handle edge cases
-        if a_off.x >= 0.0 && b_off.x < 0.0 {
-            return Ordering::Less;
-        }
+/// Whether triangles need to be wound in reverse to keep facing outwards
+pub fn map_is_mirrored(settings: &MapLoaderSettings) -> bool {
+    settings.handedness == Handedness::Left
+}
 
-        if a_off.x < 0.0 && b_off.x >= 0.0 {
-            return Ordering::Greater;
-        }
+#[cfg(test)]
+mod tests {
+    use super::{map_to_bevy_position, map_to_bevy_rotation, map_to_bevy_space3};
+    use crate::{Handedness, MapLoaderSettings, UpAxis};
+    use glam::{Quat, Vec3};
 
-        if a_off.x == 0.0 && b_off.x == 0.0 {
-            if a_off.y >= 0.0 || b_off.y >= 0.0 {
-                if a_proj.y > b_proj.y {
-                    return Ordering::Less;
-                }
+    fn all_settings() -> Vec<MapLoaderSettings> {
+        let mut all_settings = Vec::new();
 
-                return Ordering::Greater;
+        for up_axis in [UpAxis::X, UpAxis::Y, UpAxis::Z] {
+            for handedness in [Handedness::Right, Handedness::Left] {
+                all_settings.push(MapLoaderSettings {
+                    up_axis,
+                    handedness,
+                    ..Default::default()
+                });
             }
+        }
 
-            if b_proj.y > a_proj.y {
-                return Ordering::Less;
-            }
+        all_settings
+    }
 
-            return Ordering::Greater;
-        }
+    #[test]
+    fn test_map_to_bevy_space() {
+        let settings = MapLoaderSettings::default();
+
+        assert_eq!(
+            map_to_bevy_space3(&Vec3::new(1.0, 2.0, 3.0), &settings),
+            Vec3::new(2.0, 3.0, 1.0)
+        );
 
-        // | a.x b.x |
-        // | a.y b.y |
-        let det = a_off.x * b_off.y - b_off.x * a_off.y;
+        let settings = MapLoaderSettings {
+            units_per_meter: 32.0,
+            up_axis: UpAxis::Y,
+            handedness: Handedness::Left,
+            ..Default::default()
+        };
 
-        if det < 0.0 {
-            return Ordering::Less;
-        } else if det > 0.0 {
-            return Ordering::Greater;
+        assert_eq!(
+            map_to_bevy_position(&Vec3::new(32.0, 64.0, 16.0), &settings),
+            Vec3::new(1.0, 2.0, -0.5)
+        );
+
+        for settings in all_settings() {
+            assert_eq!(
+                map_to_bevy_space3(
+                    &[Vec3::X, Vec3::Y, Vec3::Z][settings.up_axis as usize],
+                    &settings
+                ),
+                Vec3::Y
+            );
         }
+    }
+
+    #[test]
+    fn test_map_to_bevy_rotation() {
+        let map_rotation =
+            Quat::from_rotation_z(30_f32.to_radians()) * Quat::from_rotation_y(45_f32.to_radians());
+        let map_facing = map_rotation * Vec3::X;
+        let map_up = map_rotation * Vec3::Z;
 
-        panic!("this set of points cannot be wound");
-    });
+        for settings in all_settings() {
+            let rotation = map_to_bevy_rotation(&map_rotation, &settings);
+
+            assert!(rotation.is_normalized());
+
+            // Forward
+            assert!((rotation * -Vec3::Z)
+                .abs_diff_eq(map_to_bevy_space3(&map_facing, &settings), crate::EPSILON));
+
+            // Up
+            assert!((rotation * Vec3::Y)
+                .abs_diff_eq(map_to_bevy_space3(&map_up, &settings), crate::EPSILON));
+        }
+    }
 }
@@ -0,0 +1,184 @@
+use super::{to_image, FileAssetProvider, MapAssetProvider, MapTexture};
+use crate::texture_formats::{DecodedTexture, Palette, Wad};
+use anyhow::Result as AResult;
+use bevy::{
+    asset::LoadContext,
+    pbr::StandardMaterial,
+    prelude::{AssetServer, FromWorld, Image, World},
+    render::texture::CompressedImageFormats,
+};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+};
+
+/// A `MapAssetProvider` which loads textures from the WAD2/WAD3 packages listed in the map's `wad` key.
+/// Textures missing from the WADs (and the default texture) are loaded by a `FileAssetProvider`.
+///
+/// WAD2 textures use the palette lump of any listed WAD, or the fallback provider's palette otherwise.
+/// WADs are cached for the lifetime of the provider.
+pub struct WadAssetProvider {
+    asset_server: AssetServer,
+    fallback: FileAssetProvider,
+    wads: RwLock<HashMap<PathBuf, Arc<Wad>>>,
+}
+
+impl FromWorld for WadAssetProvider {
+    fn from_world(world: &mut World) -> Self {
+        Self {
+            asset_server: world.resource::<AssetServer>().clone(),
+            fallback: FileAssetProvider::from_world(world),
+            wads: Default::default(),
+        }
+    }
+}
+
+impl WadAssetProvider {
+    /// Replaces the provider used for the default texture and textures missing from the WADs
+    pub fn with_fallback(mut self, fallback: FileAssetProvider) -> Self {
+        self.fallback = fallback;
+        self
+    }
+
+    /// Paths to try for a `wad` entry, as editors may store them relative to the map or the game directory
+    fn wad_paths(load_context: &LoadContext, wad: &str) -> Vec<PathBuf> {
+        let wad = PathBuf::from(wad.replace('\\', "/"));
+        let map_dir = load_context
+            .path()
+            .parent()
+            .unwrap_or_else(|| Path::new(""));
+
+        let mut paths = Vec::new();
+
+        if wad.is_relative() {
+            paths.push(map_dir.join(&wad));
+            paths.push(wad.clone());
+        }
+
+        if let Some(file_name) = wad.file_name() {
+            paths.push(map_dir.join(file_name));
+        }
+
+        paths
+    }
+
+    async fn load_wad(&self, load_context: &LoadContext<'_>, wad: &str) -> Option<Arc<Wad>> {
+        for path in Self::wad_paths(load_context, wad) {
+            if let Some(wad) = self.wads.read().unwrap().get(&path) {
+                return Some(wad.clone());
+            }
+
+            let bytes = match self.asset_server.asset_io().load_path(&path).await {
+                Ok(bytes) => bytes,
+                Err(_) => continue,
+            };
+
+            match Wad::parse(&bytes) {
+                Ok(wad) => {
+                    let wad = Arc::new(wad);
+                    self.wads.write().unwrap().insert(path, wad.clone());
+
+                    return Some(wad);
+                }
+                Err(err) => {
+                    bevy::log::warn!("failed to parse {}: {}", path.display(), err);
+                }
+            }
+        }
+
+        None
+    }
+
+    async fn load_palette(&self, wads: &[Arc<Wad>]) -> Option<Arc<Palette>> {
+        if let Some(palette) = wads.iter().find_map(|wad| wad.palette()) {
+            return Some(Arc::new(palette));
+        }
+
+        self.fallback.load_palette().await
+    }
+
+    pub async fn try_load_texture(
+        &self,
+        load_context: &LoadContext<'_>,
+        wads: Option<&[&str]>,
+        tex_name: &str,
+    ) -> Option<AResult<DecodedTexture>> {
+        let mut loaded_wads = Vec::new();
+
+        for wad in wads.unwrap_or_default() {
+            if let Some(wad) = self.load_wad(load_context, wad).await {
+                loaded_wads.push(wad);
+            }
+        }
+
+        let wad = loaded_wads.iter().find(|wad| wad.has_texture(tex_name))?;
+
+        let palette = if wad.needs_palette() {
+            self.load_palette(&loaded_wads).await
+        } else {
+            None
+        };
+
+        wad.texture(tex_name, palette.as_deref())
+            .map(|result| result.map_err(Into::into))
+    }
+}
+
+#[async_trait]
+impl MapAssetProvider for WadAssetProvider {
+    async fn load_default_texture(
+        &self,
+        load_context: &mut LoadContext,
+        texture_collections: Option<&[&str]>,
+        supported_compressed_formats: CompressedImageFormats,
+    ) -> AResult<MapTexture> {
+        self.fallback
+            .load_default_texture(
+                load_context,
+                texture_collections,
+                supported_compressed_formats,
+            )
+            .await
+    }
+
+    async fn load_texture(
+        &self,
+        load_context: &mut LoadContext,
+        texture_collections: Option<&[&str]>,
+        supported_compressed_formats: CompressedImageFormats,
+        tex_name: &str,
+    ) -> Option<MapTexture> {
+        match self
+            .try_load_texture(load_context, texture_collections, tex_name)
+            .await
+        {
+            Some(Ok(texture)) => Some(MapTexture::embedded(to_image(texture))),
+            Some(Err(err)) => {
+                bevy::log::warn!("failed to decode {}: {}", tex_name, err);
+                None
+            }
+            None => {
+                self.fallback
+                    .load_texture(
+                        load_context,
+                        texture_collections,
+                        supported_compressed_formats,
+                        tex_name,
+                    )
+                    .await
+            }
+        }
+    }
+
+    async fn get_material(
+        &self,
+        tex_name: &str,
+        load_context: &mut LoadContext,
+        default_tex: &Image,
+    ) -> Option<StandardMaterial> {
+        self.fallback
+            .get_material(tex_name, load_context, default_tex)
+            .await
+    }
+}
@@ -1,5 +1,54 @@
 use super::BrushFace;
 use glam::DVec3;
+use std::collections::HashSet;
+
+/// Half the size of the initial polygon of each face, which must contain the whole brush
+const MAX_EXTENT: f64 = 1_048_576.0;
+
+/// The vertices of a brush, and the polygon of each face
+#[derive(Debug)]
+pub struct BrushGeometry {
+    /// Unique vertices, with near-duplicates merged
+    pub vertices: Vec<DVec3>,
+    /// Indices into `vertices` for each face, in the same order as `Brush::faces`.
+    /// Polygons are convex and wound counter-clockwise around the face's normal.
+    /// Faces which don't contribute to the brush (e.g. duplicate planes) have no vertices.
+    pub faces: Vec<Vec<usize>>,
+}
+
+impl BrushGeometry {
+    pub fn face_vertices(&self, face_idx: usize) -> impl Iterator<Item = DVec3> + '_ {
+        self.faces[face_idx].iter().map(|idx| self.vertices[*idx])
+    }
+
+    /// Whether every edge is shared by two faces, in opposite directions.
+    /// Open brushes have faces which still reach the edges of their initial quads.
+    fn is_closed(&self) -> bool {
+        let edges = self
+            .faces
+            .iter()
+            .flat_map(|face| (0..face.len()).map(|i| (face[i], face[(i + 1) % face.len()])))
+            .collect::<Vec<_>>();
+
+        let unique = edges.iter().copied().collect::<HashSet<_>>();
+
+        unique.len() == edges.len() && edges.iter().all(|(a, b)| unique.contains(&(*b, *a)))
+    }
+
+    fn vertex_index(&mut self, vertex: DVec3) -> usize {
+        match self
+            .vertices
+            .iter()
+            .position(|v| v.abs_diff_eq(vertex, crate::EPSILON_64))
+        {
+            Some(idx) => idx,
+            None => {
+                self.vertices.push(vertex);
+                self.vertices.len() - 1
+            }
+        }
+    }
+}
 
 #[derive(PartialEq, Debug)]
 pub struct Brush {
@@ -7,6 +56,88 @@ pub struct Brush {
 }
 
 impl Brush {
+    pub fn serialize(&self) -> String {
+        let mut output = String::from("{\n");
+
+        for face in &self.faces {
+            output.push_str(&face.serialize());
+            output.push('\n');
+        }
+
+        output.push('}');
+
+        output
+    }
+
+    /// Builds the polygon of each face, by clipping a huge quad on its plane by every other face.
+    /// Returns `None` if the brush doesn't enclose a volume.
+    pub fn geometry(&self) -> Option<BrushGeometry> {
+        let planes = self.planes();
+
+        let mut geometry = BrushGeometry {
+            vertices: Vec::new(),
+            faces: Vec::with_capacity(self.faces.len()),
+        };
+
+        for (i, (normal, dist)) in planes.iter().enumerate() {
+            let mut polygon = if normal.is_finite() {
+                base_polygon(*normal, *dist)
+            } else {
+                Vec::new()
+            };
+
+            for (j, (clip_normal, clip_dist)) in planes.iter().enumerate() {
+                if i == j || polygon.is_empty() || !clip_normal.is_finite() {
+                    continue;
+                }
+
+                // Only the first of several faces on the same plane is kept
+                if j < i
+                    && clip_normal.abs_diff_eq(*normal, crate::EPSILON_64)
+                    && (clip_dist - dist).abs() < crate::EPSILON_64
+                {
+                    polygon.clear();
+                    break;
+                }
+
+                polygon = clip_polygon(&polygon, *clip_normal, *clip_dist);
+            }
+
+            merge_vertices(&mut polygon);
+
+            let indices = if polygon_area(&polygon, *normal) > crate::EPSILON_64 {
+                polygon
+                    .into_iter()
+                    .map(|vertex| geometry.vertex_index(vertex))
+                    .collect()
+            } else {
+                Vec::new()
+            };
+
+            geometry.faces.push(indices);
+        }
+
+        // A closed convex polyhedron has at least 4 faces
+        let num_polygons = geometry.faces.iter().filter(|f| !f.is_empty()).count();
+
+        if num_polygons < 4 || !geometry.is_closed() {
+            return None;
+        }
+
+        Some(geometry)
+    }
+
+    /// The plane of each face, with a unit normal
+    pub(crate) fn planes(&self) -> Vec<(DVec3, f64)> {
+        self.faces
+            .iter()
+            .map(|face| {
+                let length = face.normal.length();
+                (face.normal / length, face.origin_dist / length)
+            })
+            .collect()
+    }
+
     pub fn contains(&self, point: DVec3) -> bool {
         // This works because brushes must be convex
         for face in &self.faces {
@@ -19,11 +150,189 @@ impl Brush {
     }
 }
 
+/// A square on the plane, wound counter-clockwise around `normal`
+fn base_polygon(normal: DVec3, dist: f64) -> Vec<DVec3> {
+    let u = normal.any_orthonormal_vector() * MAX_EXTENT;
+    let v = normal.cross(u);
+    let center = normal * dist;
+
+    vec![
+        center - u - v,
+        center + u - v,
+        center + u + v,
+        center - u + v,
+    ]
+}
+
+/// Keeps the part of a convex polygon behind a plane (Sutherland-Hodgman)
+pub(crate) fn clip_polygon(polygon: &[DVec3], normal: DVec3, dist: f64) -> Vec<DVec3> {
+    let distances = polygon
+        .iter()
+        .map(|v| normal.dot(*v) - dist)
+        .collect::<Vec<_>>();
+
+    let mut clipped = Vec::with_capacity(polygon.len() + 1);
+
+    for i in 0..polygon.len() {
+        let j = (i + 1) % polygon.len();
+        let (a, b) = (polygon[i], polygon[j]);
+        let (da, db) = (distances[i], distances[j]);
+
+        if da <= crate::EPSILON_64 {
+            clipped.push(a);
+        }
+
+        // Points within epsilon of the plane are kept as they are, rather than split
+        if (da < -crate::EPSILON_64 && db > crate::EPSILON_64)
+            || (da > crate::EPSILON_64 && db < -crate::EPSILON_64)
+        {
+            clipped.push(a + (b - a) * (da / (da - db)));
+        }
+    }
+
+    clipped
+}
+
+/// Removes consecutive vertices which are nearly the same
+fn merge_vertices(polygon: &mut Vec<DVec3>) {
+    polygon.dedup_by(|b, a| a.abs_diff_eq(*b, crate::EPSILON_64));
+
+    while polygon.len() > 1 && polygon[0].abs_diff_eq(polygon[polygon.len() - 1], crate::EPSILON_64)
+    {
+        polygon.pop();
+    }
+}
+
+pub(crate) fn polygon_area(polygon: &[DVec3], normal: DVec3) -> f64 {
+    if polygon.len() < 3 {
+        return 0.0;
+    }
+
+    let mut area = DVec3::ZERO;
+
+    for i in 1..(polygon.len() - 1) {
+        area += (polygon[i] - polygon[0]).cross(polygon[i + 1] - polygon[0]);
+    }
+
+    area.dot(normal) / 2.0
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::test_utils::get_brush;
+    use super::{Brush, BrushGeometry};
+    use crate::test_utils::{get_brush, get_cylinder, plane_face as face};
     use glam::DVec3;
 
+    fn assert_wound(brush: &Brush, geometry: &BrushGeometry) {
+        for (face_idx, face) in brush.faces.iter().enumerate() {
+            let vertices = geometry.face_vertices(face_idx).collect::<Vec<_>>();
+
+            for i in 1..vertices.len().saturating_sub(1) {
+                let normal = (vertices[i] - vertices[0]).cross(vertices[i + 1] - vertices[0]);
+                assert!(normal.dot(face.normal) > 0.0);
+            }
+        }
+    }
+
+    fn face_sizes(geometry: &BrushGeometry) -> Vec<usize> {
+        geometry.faces.iter().map(|face| face.len()).collect()
+    }
+
+    #[test]
+    fn test_geometry() {
+        let brush = get_brush();
+        let geometry = brush.geometry().expect("brush is degenerate");
+
+        assert_eq!(geometry.vertices.len(), 8);
+        assert_eq!(face_sizes(&geometry), vec![4; 6]);
+        assert!(geometry
+            .vertices
+            .iter()
+            .all(|v| v.abs().abs_diff_eq(DVec3::splat(16.0), crate::EPSILON_64)));
+        assert_wound(&brush, &geometry);
+    }
+
+    #[test]
+    fn test_geometry_wedge() {
+        let brush = Brush {
+            faces: vec![
+                face(-DVec3::Z, 0.0),
+                face(-DVec3::X, 0.0),
+                face(-DVec3::Y, 0.0),
+                face(DVec3::Y, 64.0),
+                face(DVec3::new(1.0, 0.0, 1.0), 64.0 / 2_f64.sqrt()),
+            ],
+        };
+
+        let geometry = brush.geometry().expect("wedge is degenerate");
+
+        assert_eq!(geometry.vertices.len(), 6);
+        assert_eq!(face_sizes(&geometry), vec![4, 4, 3, 3, 4]);
+        assert_wound(&brush, &geometry);
+    }
+
+    #[test]
+    fn test_geometry_cylinder() {
+        let sides = 24;
+
+        let mut brush = get_cylinder(sides);
+        // Duplicate planes don't produce any polygons
+        brush.faces.push(face(DVec3::Z, 32.0));
+
+        let geometry = brush.geometry().expect("cylinder is degenerate");
+
+        let mut expected_sizes = vec![4; sides];
+        expected_sizes.extend([sides, sides, 0]);
+
+        assert_eq!(geometry.vertices.len(), sides * 2);
+        assert_eq!(face_sizes(&geometry), expected_sizes);
+        assert_wound(&brush, &geometry);
+    }
+
+    #[test]
+    fn test_geometry_degenerate() {
+        // Flat
+        let brush = Brush {
+            faces: vec![
+                face(DVec3::Z, 0.0),
+                face(-DVec3::Z, 0.0),
+                face(DVec3::X, 16.0),
+                face(-DVec3::X, 16.0),
+                face(DVec3::Y, 16.0),
+                face(-DVec3::Y, 16.0),
+            ],
+        };
+
+        assert!(brush.geometry().is_none());
+
+        // Open
+        let brush = Brush {
+            faces: vec![
+                face(DVec3::Z, 0.0),
+                face(DVec3::X, 16.0),
+                face(DVec3::Y, 16.0),
+            ],
+        };
+
+        assert!(brush.geometry().is_none());
+
+        // A box without a top
+        let mut brush = get_brush();
+        let top = brush
+            .faces
+            .iter()
+            .position(|face| {
+                face.normal
+                    .normalize()
+                    .abs_diff_eq(DVec3::Z, crate::EPSILON_64)
+            })
+            .unwrap();
+        brush.faces.remove(top);
+
+        assert_eq!(brush.faces.len(), 5);
+        assert!(brush.geometry().is_none());
+    }
+
     #[test]
     fn test_brush_contains() {
         let brush = get_brush();
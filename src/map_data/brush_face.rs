@@ -1,5 +1,49 @@
 use super::UvAxis;
-use glam::{DVec3, DVec4};
+use glam::{const_dvec3, DVec3, DVec4};
+
+/// Quake's paraxial texture projection: (plane normal, u axis, v axis) for each axis-aligned plane
+/// (`baseaxis` in qbsp's `TextureAxisFromPlane`)
+const BASE_AXES: [[DVec3; 3]; 6] = [
+    [
+        const_dvec3!([0.0, 0.0, 1.0]),
+        const_dvec3!([1.0, 0.0, 0.0]),
+        const_dvec3!([0.0, -1.0, 0.0]),
+    ],
+    [
+        const_dvec3!([0.0, 0.0, -1.0]),
+        const_dvec3!([1.0, 0.0, 0.0]),
+        const_dvec3!([0.0, -1.0, 0.0]),
+    ],
+    [
+        const_dvec3!([1.0, 0.0, 0.0]),
+        const_dvec3!([0.0, 1.0, 0.0]),
+        const_dvec3!([0.0, 0.0, -1.0]),
+    ],
+    [
+        const_dvec3!([-1.0, 0.0, 0.0]),
+        const_dvec3!([0.0, 1.0, 0.0]),
+        const_dvec3!([0.0, 0.0, -1.0]),
+    ],
+    [
+        const_dvec3!([0.0, 1.0, 0.0]),
+        const_dvec3!([1.0, 0.0, 0.0]),
+        const_dvec3!([0.0, 0.0, -1.0]),
+    ],
+    [
+        const_dvec3!([0.0, -1.0, 0.0]),
+        const_dvec3!([1.0, 0.0, 0.0]),
+        const_dvec3!([0.0, 0.0, -1.0]),
+    ],
+];
+
+/// Quake 2/Quake 3 face attributes, appended to each face as `content surface value`.
+/// Flag bits correspond to the order of `FaceAttributes` in the game config.
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub struct SurfaceAttributes {
+    pub content_flags: i32,
+    pub surface_flags: i32,
+    pub surface_value: f32,
+}
 
 #[derive(PartialEq, Debug)]
 pub struct BrushFace {
@@ -17,6 +61,8 @@ pub struct BrushFace {
     pub rotation: f32,
     pub x_scale: f32,
     pub y_scale: f32,
+    /// Only present in Quake 2 and Quake 3 format maps
+    pub surface: Option<SurfaceAttributes>,
 
     pub normal: DVec3,
     pub origin_dist: f64,
@@ -45,11 +91,115 @@ impl BrushFace {
             rotation,
             x_scale,
             y_scale,
+            surface: None,
             normal,
             origin_dist,
         }
     }
 
+    /// Serializes the face as one line of a Valve 220 (or Quake 2/Quake 3 Valve) brush
+    pub fn serialize(&self) -> String {
+        let points = self
+            .points
+            .iter()
+            .map(|p| format!("( {} {} {} )", p.x, p.y, p.z))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let mut output = format!(
+            "{} {} {} {} {} {} {}",
+            points,
+            self.texture,
+            self.u.serialize(),
+            self.v.serialize(),
+            self.rotation,
+            self.x_scale,
+            self.y_scale
+        );
+
+        if let Some(surface) = &self.surface {
+            output.push_str(&format!(
+                " {} {} {}",
+                surface.content_flags, surface.surface_flags, surface.surface_value
+            ));
+        }
+
+        output
+    }
+
+    /// Creates a face from the Standard (id Quake) format,
+    /// deriving the UV axes from the face normal the same way Quake's tools do
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_standard(
+        points: [DVec3; 3],
+        texture: String,
+        x_offset: f64,
+        y_offset: f64,
+        rotation: f32,
+        x_scale: f32,
+        y_scale: f32,
+    ) -> BrushFace {
+        let normal = (points[2] - points[0]).cross(points[1] - points[0]);
+        let [u, v] = Self::standard_uv_axes(normal, rotation);
+
+        BrushFace::new(
+            points,
+            texture,
+            UvAxis {
+                axis: u,
+                offset: x_offset,
+            },
+            UvAxis {
+                axis: v,
+                offset: y_offset,
+            },
+            rotation,
+            x_scale,
+            y_scale,
+        )
+    }
+
+    /// Picks the axis-aligned projection closest to `normal`, then rotates it by `rotation` degrees
+    fn standard_uv_axes(normal: DVec3, rotation: f32) -> [DVec3; 2] {
+        let mut best = 0;
+        let mut best_dot = 0.0;
+
+        for (i, axes) in BASE_AXES.iter().enumerate() {
+            let dot = normal.dot(axes[0]);
+
+            if dot > best_dot {
+                best = i;
+                best_dot = dot;
+            }
+        }
+
+        let mut uv_axes = [BASE_AXES[best][1], BASE_AXES[best][2]];
+
+        // Exact values for the common cases, as in Quake
+        let (sin, cos) = match rotation {
+            0.0 => (0.0, 1.0),
+            90.0 => (1.0, 0.0),
+            180.0 => (0.0, -1.0),
+            270.0 => (-1.0, 0.0),
+            r => (r as f64).to_radians().sin_cos(),
+        };
+
+        // Rotate within the plane of the two non-zero components
+        let nonzero = |axis: DVec3| (0..3).find(|&i| axis[i] != 0.0).unwrap_or(2);
+        let u_idx = nonzero(uv_axes[0]);
+        let v_idx = nonzero(uv_axes[1]);
+
+        for axis in &mut uv_axes {
+            let u = cos * axis[u_idx] - sin * axis[v_idx];
+            let v = sin * axis[u_idx] + cos * axis[v_idx];
+
+            axis[u_idx] = u;
+            axis[v_idx] = v;
+        }
+
+        uv_axes
+    }
+
     pub fn intersect_faces(&self, f2: &BrushFace, f3: &BrushFace) -> Option<DVec3> {
         // https://math.stackexchange.com/a/3734749 (IDK how this works)
         let determinant = self.normal.dot(f2.normal.cross(f3.normal));
@@ -80,6 +230,7 @@ impl BrushFace {
 
 #[cfg(test)]
 mod tests {
+    use super::BrushFace;
     use crate::test_utils::get_brush;
     use glam::DVec3;
 
@@ -94,6 +245,31 @@ mod tests {
         assert!(intersect.abs_diff_eq(DVec3::new(-16.0, -16.0, -16.0), crate::EPSILON_64))
     }
 
+    #[test]
+    fn test_standard_uv_axes() {
+        // Floor
+        assert_eq!(
+            BrushFace::standard_uv_axes(DVec3::new(0.1, 0.0, 1.0), 0.0),
+            [DVec3::X, DVec3::new(0.0, -1.0, 0.0)]
+        );
+
+        // Wall facing -Y
+        assert_eq!(
+            BrushFace::standard_uv_axes(DVec3::new(0.0, -2.0, 1.0), 0.0),
+            [DVec3::X, DVec3::new(0.0, 0.0, -1.0)]
+        );
+
+        // Floor, rotated
+        let [u, v] = BrushFace::standard_uv_axes(DVec3::Z, 90.0);
+        assert!(u.abs_diff_eq(DVec3::Y, crate::EPSILON_64));
+        assert!(v.abs_diff_eq(DVec3::X, crate::EPSILON_64));
+
+        let [u, v] = BrushFace::standard_uv_axes(DVec3::X, 45.0);
+        let half_sqrt2 = 0.5_f64.sqrt();
+        assert!(u.abs_diff_eq(DVec3::new(0.0, half_sqrt2, half_sqrt2), crate::EPSILON_64));
+        assert!(v.abs_diff_eq(DVec3::new(0.0, half_sqrt2, -half_sqrt2), crate::EPSILON_64));
+    }
+
     #[test]
     fn test_tangent() {
         let brush = get_brush();
@@ -0,0 +1,293 @@
+use super::{BrushSources, Entity, Map};
+use std::collections::{HashMap, HashSet};
+
+/// Layers and groups have separate ids, so they are identified by their kind and id together
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum MapGroupKind {
+    Layer,
+    Group,
+}
+
+/// A TrenchBroom layer or group, stored as a `func_group` entity with a `_tb_type`.
+/// Brushes in the layer or group belong to that entity, and other entities refer to it
+/// with a `_tb_layer` or `_tb_group` key.
+#[derive(Clone, PartialEq, Debug)]
+pub struct MapGroup {
+    pub kind: MapGroupKind,
+    /// `_tb_id`
+    pub id: u32,
+    /// `_tb_name`
+    pub name: String,
+    /// The index of the `func_group` entity in `Map::entities`
+    pub entity: usize,
+    /// The containing layer or group, or `None` in the default layer
+    pub parent: Option<(MapGroupKind, u32)>,
+    /// Hidden in the editor (`_tb_layer_hidden`)
+    pub hidden: bool,
+    /// Not meant to be part of the game (`_tb_layer_omit_from_export`)
+    pub omit_from_export: bool,
+}
+
+impl MapGroup {
+    /// The kind and id together, as referred to by `Entity::tb_parent`
+    pub fn key(&self) -> (MapGroupKind, u32) {
+        (self.kind, self.id)
+    }
+}
+
+impl Entity {
+    /// The layer or group this entity is in, or `None` in the default layer
+    pub fn tb_parent(&self) -> Option<(MapGroupKind, u32)> {
+        self.u32_property("_tb_group")
+            .map(|id| (MapGroupKind::Group, id))
+            .or_else(|| {
+                self.u32_property("_tb_layer")
+                    .map(|id| (MapGroupKind::Layer, id))
+            })
+    }
+
+    fn u32_property(&self, key: &str) -> Option<u32> {
+        self.properties.get(key)?.trim().parse().ok()
+    }
+
+    fn flag_property(&self, key: &str) -> bool {
+        self.properties.get(key).is_some_and(|value| value == "1")
+    }
+
+    /// Reads the layer or group stored in this entity, if it is one
+    pub fn tb_group(&self, entity_idx: usize) -> Option<MapGroup> {
+        let kind = match self.properties.get("_tb_type")?.as_str() {
+            "_tb_layer" => MapGroupKind::Layer,
+            "_tb_group" => MapGroupKind::Group,
+            _ => return None,
+        };
+
+        Some(MapGroup {
+            kind,
+            id: self.u32_property("_tb_id")?,
+            name: self.properties.get("_tb_name").cloned().unwrap_or_default(),
+            entity: entity_idx,
+            parent: self.tb_parent(),
+            hidden: self.flag_property("_tb_layer_hidden"),
+            omit_from_export: self.flag_property("_tb_layer_omit_from_export"),
+        })
+    }
+}
+
+impl Map {
+    /// The layers and groups of the map, in the order of their entities
+    pub fn groups(&self) -> Vec<MapGroup> {
+        self.entities
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, entity)| entity.tb_group(idx))
+            .collect()
+    }
+
+    /// Removes layers marked omit-from-export, along with everything in them.
+    /// Returns the indices each remaining brush had before.
+    pub fn remove_omitted_layers(&mut self) -> BrushSources {
+        let groups = self.groups();
+
+        let parents = groups
+            .iter()
+            .map(|group| (group.key(), group.parent))
+            .collect::<HashMap<_, _>>();
+
+        let omitted = groups
+            .iter()
+            .filter(|group| group.omit_from_export)
+            .map(MapGroup::key)
+            .collect::<HashSet<_>>();
+
+        if omitted.is_empty() {
+            return self.brush_sources();
+        }
+
+        let is_omitted = |entity: &Entity| {
+            let mut key = match entity.tb_group(0) {
+                Some(group) => Some(group.key()),
+                None => entity.tb_parent(),
+            };
+
+            // Limited to the number of groups, in case of cycles
+            for _ in 0..=groups.len() {
+                match key {
+                    Some(group_key) if omitted.contains(&group_key) => return true,
+                    Some(group_key) => key = parents.get(&group_key).copied().flatten(),
+                    None => break,
+                }
+            }
+
+            false
+        };
+
+        let sources = self
+            .brush_sources()
+            .into_iter()
+            .zip(&self.entities)
+            .filter(|(_, entity)| !is_omitted(entity))
+            .map(|(sources, _)| sources)
+            .collect();
+
+        self.entities.retain(|entity| !is_omitted(entity));
+
+        sources
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MapGroupKind::{Group, Layer};
+    use crate::{parsing::parse_map, test_utils::get_brush};
+
+    const GROUPS: &str = r#"
+{
+"classname" "worldspawn"
+}
+{
+"classname" "func_group"
+"_tb_type" "_tb_layer"
+"_tb_name" "Lights"
+"_tb_id" "1"
+"_tb_layer_hidden" "1"
+}
+{
+"classname" "func_group"
+"_tb_type" "_tb_group"
+"_tb_name" "Lamp"
+"_tb_id" "2"
+"_tb_layer" "1"
+}
+{
+"classname" "light"
+"_tb_group" "2"
+}
+{
+"classname" "func_group"
+"_tb_type" "_tb_layer"
+"_tb_name" "Notes"
+"_tb_id" "3"
+"_tb_layer_omit_from_export" "1"
+}
+{
+"classname" "func_group"
+"_tb_type" "_tb_group"
+"_tb_name" "Arrows"
+"_tb_id" "4"
+"_tb_layer" "3"
+}
+{
+"classname" "info_null"
+"_tb_group" "4"
+}
+{
+"classname" "info_null"
+"_tb_layer" "3"
+}
+{
+"classname" "info_player_start"
+}
+"#;
+
+    #[test]
+    fn test_groups() {
+        let map = parse_map::<()>(GROUPS).expect("failed to parse").1;
+        let groups = map.groups();
+
+        assert_eq!(groups.len(), 4);
+
+        assert_eq!(groups[0].kind, Layer);
+        assert_eq!(groups[0].name, "Lights");
+        assert_eq!(groups[0].entity, 1);
+        assert_eq!(groups[0].parent, None);
+        assert!(groups[0].hidden);
+
+        assert_eq!(groups[1].kind, Group);
+        assert_eq!(groups[1].parent, Some((Layer, 1)));
+        assert_eq!(map.entities[3].tb_parent(), Some((Group, 2)));
+
+        assert!(groups[2].omit_from_export);
+        assert!(!groups[3].omit_from_export);
+    }
+
+    #[test]
+    fn test_remove_omitted_layers() {
+        let mut map = parse_map::<()>(GROUPS).expect("failed to parse").1;
+        map.entities[8].brushes.push(get_brush());
+
+        let sources = map.remove_omitted_layers();
+
+        let classnames = map
+            .entities
+            .iter()
+            .map(|entity| entity.classname().unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            classnames,
+            vec![
+                "worldspawn",
+                "func_group",
+                "func_group",
+                "light",
+                "info_player_start"
+            ]
+        );
+
+        // Brushes keep their indices from before the layer was removed
+        assert_eq!(sources[4], vec![(8, 0)]);
+    }
+
+    #[test]
+    fn test_shared_ids() {
+        let mut map = parse_map::<()>(
+            r#"
+{
+"classname" "worldspawn"
+}
+{
+"classname" "func_group"
+"_tb_type" "_tb_layer"
+"_tb_name" "Notes"
+"_tb_id" "1"
+"_tb_layer_omit_from_export" "1"
+}
+{
+"classname" "func_group"
+"_tb_type" "_tb_group"
+"_tb_name" "Lamp"
+"_tb_id" "1"
+}
+{
+"classname" "light"
+"_tb_group" "1"
+}
+{
+"classname" "info_null"
+"_tb_layer" "1"
+}
+"#,
+        )
+        .expect("failed to parse")
+        .1;
+
+        let groups = map.groups();
+        assert_eq!(groups[0].key(), (Layer, 1));
+        assert_eq!(groups[1].key(), (Group, 1));
+        assert_eq!(map.entities[3].tb_parent(), Some((Group, 1)));
+        assert_eq!(map.entities[4].tb_parent(), Some((Layer, 1)));
+
+        // Only the layer and its entities are removed
+        map.remove_omitted_layers();
+
+        let classnames = map
+            .entities
+            .iter()
+            .map(|entity| entity.classname().unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(classnames, vec!["worldspawn", "func_group", "light"]);
+        assert_eq!(map.groups()[0].name, "Lamp");
+    }
+}
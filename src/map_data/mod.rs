@@ -1,4 +1,4 @@
-use glam::DVec3;
+use glam::{DQuat, DVec3};
 use std::collections::HashMap;
 
 mod brush;
@@ -7,31 +7,388 @@ pub use brush::*;
 mod brush_face;
 pub use brush_face::*;
 
+mod groups;
+pub use groups::*;
+
+mod patch;
+pub use patch::*;
+
 #[derive(PartialEq, Debug)]
 pub struct UvAxis {
     pub axis: DVec3,
     pub offset: f64,
 }
 
+impl UvAxis {
+    pub fn serialize(&self) -> String {
+        format!(
+            "[ {} {} {} {} ]",
+            self.axis.x, self.axis.y, self.axis.z, self.offset
+        )
+    }
+}
+
+/// Escapes a property key or value such that `parsing` reads it back unchanged
+fn escape_string(value: &str) -> String {
+    let mut output = String::with_capacity(value.len() + 2);
+    output.push('"');
+
+    for c in value.chars() {
+        match c {
+            '\\' | '"' | '\n' => output.push('\\'),
+            _ => (),
+        }
+
+        output.push(c);
+    }
+
+    output.push('"');
+    output
+}
+
 #[derive(PartialEq, Debug)]
 pub struct Entity {
     pub properties: HashMap<String, String>,
     pub brushes: Vec<Brush>,
+    pub patches: Vec<Patch>,
 }
 
+impl Entity {
+    pub fn classname(&self) -> Option<&str> {
+        self.properties.get("classname").map(|prop| prop.as_str())
+    }
+
+    /// Serializes the entity in Valve 220 format.
+    /// `classname` is written first, followed by the other properties in alphabetical order.
+    pub fn serialize(&self) -> String {
+        let mut output = String::from("{\n");
+
+        let mut properties = self.properties.iter().collect::<Vec<_>>();
+        properties.sort_by_key(|(key, _)| (key.as_str() != "classname", key.as_str()));
+
+        for (key, value) in properties {
+            output.push_str(&escape_string(key));
+            output.push(' ');
+            output.push_str(&escape_string(value));
+            output.push('\n');
+        }
+
+        for (idx, brush) in self.brushes.iter().enumerate() {
+            output.push_str(&format!("// brush {}\n", idx));
+            output.push_str(&brush.serialize());
+            output.push('\n');
+        }
+
+        // Radiant numbers patches along with brushes
+        for (idx, patch) in self.patches.iter().enumerate() {
+            output.push_str(&format!("// brush {}\n", self.brushes.len() + idx));
+            output.push_str(&patch.serialize());
+            output.push('\n');
+        }
+
+        output.push('}');
+
+        output
+    }
+
+    /// Parses a property made of three whitespace-separated numbers, such as `origin` or `angles`
+    pub fn vec3_property(&self, key: &str) -> Option<DVec3> {
+        let components = self
+            .properties
+            .get(key)?
+            .split_whitespace()
+            .map(|c| c.parse::<f64>().ok())
+            .collect::<Option<Vec<_>>>()?;
+
+        match components[..] {
+            [x, y, z] => Some(DVec3::new(x, y, z)),
+            _ => None,
+        }
+    }
+
+    /// The position of a point entity, in .map space
+    pub fn origin(&self) -> Option<DVec3> {
+        self.vec3_property("origin")
+    }
+
+    /// The orientation of a point entity in .map space, such that the entity faces +X when unrotated.
+    /// Checks `angles` (pitch yaw roll), then `mangle`, then `angle` (yaw, or -1/-2 for up/down).
+    /// `mangle` is pitch yaw roll, except on lights where it follows the ericw-tools yaw pitch roll order.
+    /// Positive pitch looks down, except for light `mangle`s where it looks up.
+    pub fn rotation(&self) -> Option<DQuat> {
+        let (pitch, yaw, roll) = if let Some(angles) = self.vec3_property("angles") {
+            (angles.x, angles.y, angles.z)
+        } else if let Some(mangle) = self.vec3_property("mangle") {
+            if self.classname().unwrap_or_default().starts_with("light") {
+                (-mangle.y, mangle.x, mangle.z)
+            } else {
+                (mangle.x, mangle.y, mangle.z)
+            }
+        } else {
+            let angle = self.properties.get("angle")?.trim().parse::<f64>().ok()?;
+
+            match angle as i32 {
+                -1 => (-90.0, 0.0, 0.0),
+                -2 => (90.0, 0.0, 0.0),
+                _ => (0.0, angle, 0.0),
+            }
+        };
+
+        Some(
+            DQuat::from_rotation_z(yaw.to_radians())
+                * DQuat::from_rotation_y(pitch.to_radians())
+                * DQuat::from_rotation_x(roll.to_radians()),
+        )
+    }
+}
+
+/// The `(entity, brush)` indices each brush had before a pass which moves brushes around,
+/// by the entity and brush indices after it
+pub type BrushSources = Vec<Vec<(usize, usize)>>;
+
 #[derive(PartialEq, Debug)]
 pub struct Map {
     pub entities: Vec<Entity>,
 }
 
 impl Map {
+    /// Serializes the map as TrenchBroom-compatible Valve 220 text
+    pub fn serialize(&self) -> String {
+        let mut output = String::from("// Format: Valve\n");
+
+        for (idx, entity) in self.entities.iter().enumerate() {
+            output.push_str(&format!("// entity {}\n", idx));
+            output.push_str(&entity.serialize());
+            output.push('\n');
+        }
+
+        output
+    }
+
     pub fn worldspawn(&self) -> Option<&Entity> {
-        self.entities.iter().find(|e| {
-            if let Some("worldspawn") = e.properties.get("classname").map(|prop| prop.as_str()) {
-                return true;
+        self.entities
+            .iter()
+            .find(|e| e.classname() == Some("worldspawn"))
+    }
+
+    /// The current indices of every brush, as if no brushes had moved
+    pub fn brush_sources(&self) -> BrushSources {
+        self.entities
+            .iter()
+            .enumerate()
+            .map(|(entity_idx, entity)| {
+                (0..entity.brushes.len())
+                    .map(|brush_idx| (entity_idx, brush_idx))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Moves the brushes and patches of entities with any of the given classnames into the
+    /// TrenchBroom layer or group they are in, or worldspawn otherwise, and removes those entities.
+    /// Does nothing if there is no worldspawn.
+    /// TrenchBroom layers and groups are kept (see `groups`),
+    /// as are entities with their own smoothing (`_phong` or `_phong_angle`).
+    ///
+    /// Returns the indices each brush had before folding.
+    pub fn fold_into_worldspawn(&mut self, classnames: &[impl AsRef<str>]) -> BrushSources {
+        let is_folded = |entity: &Entity| {
+            entity
+                .classname()
+                .is_some_and(|classname| classnames.iter().any(|c| c.as_ref() == classname))
+                && !["_tb_type", "_phong", "_phong_angle"]
+                    .iter()
+                    .any(|key| entity.properties.contains_key(*key))
+        };
+
+        let worldspawn_idx = match self
+            .entities
+            .iter()
+            .position(|e| e.classname() == Some("worldspawn"))
+        {
+            Some(idx) => idx,
+            None => return self.brush_sources(),
+        };
+
+        let mut sources = self.brush_sources();
+
+        let groups = self
+            .groups()
+            .into_iter()
+            .map(|group| (group.key(), group.entity))
+            .collect::<HashMap<_, _>>();
+
+        for idx in 0..self.entities.len() {
+            if !is_folded(&self.entities[idx]) {
+                continue;
             }
 
-            false
-        })
+            // Layers and groups are never folded themselves, so they can receive brushes
+            let target = self.entities[idx]
+                .tb_parent()
+                .and_then(|key| groups.get(&key).copied())
+                .unwrap_or(worldspawn_idx);
+
+            let mut brushes = std::mem::take(&mut self.entities[idx].brushes);
+            let mut patches = std::mem::take(&mut self.entities[idx].patches);
+
+            self.entities[target].brushes.append(&mut brushes);
+            self.entities[target].patches.append(&mut patches);
+
+            let mut brush_sources = std::mem::take(&mut sources[idx]);
+            sources[target].append(&mut brush_sources);
+        }
+
+        let kept = self
+            .entities
+            .iter()
+            .map(|e| !is_folded(e))
+            .collect::<Vec<_>>();
+
+        self.entities.retain(|e| !is_folded(e));
+
+        sources
+            .into_iter()
+            .zip(kept)
+            .filter_map(|(sources, kept)| kept.then_some(sources))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Entity, Map};
+    use crate::{parsing::parse_map, test_utils};
+    use glam::DVec3;
+    use std::collections::HashMap;
+
+    fn entity(properties: &[(&str, &str)]) -> Entity {
+        Entity {
+            properties: properties
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<HashMap<_, _>>(),
+            brushes: Vec::new(),
+            patches: Vec::new(),
+        }
+    }
+
+    fn parse(text: &str) -> Map {
+        parse_map::<nom::error::VerboseError<&str>>(text)
+            .expect("failed to parse")
+            .1
+    }
+
+    fn forward(entity: &Entity) -> DVec3 {
+        entity.rotation().expect("no rotation") * DVec3::X
+    }
+
+    #[test]
+    fn test_origin() {
+        assert_eq!(
+            entity(&[("origin", "16 -32 8.5")]).origin(),
+            Some(DVec3::new(16.0, -32.0, 8.5))
+        );
+
+        assert_eq!(entity(&[("origin", "16 -32")]).origin(), None);
+        assert_eq!(entity(&[("origin", "a b c")]).origin(), None);
+        assert_eq!(entity(&[]).origin(), None);
+    }
+
+    #[test]
+    fn test_rotation() {
+        assert!(entity(&[]).rotation().is_none());
+
+        assert!(forward(&entity(&[("angle", "90")])).abs_diff_eq(DVec3::Y, crate::EPSILON_64));
+        assert!(forward(&entity(&[("angle", "-1")])).abs_diff_eq(DVec3::Z, crate::EPSILON_64));
+        assert!(forward(&entity(&[("angle", "-2")])).abs_diff_eq(-DVec3::Z, crate::EPSILON_64));
+
+        // pitch yaw roll, positive pitch looks down
+        assert!(forward(&entity(&[("angles", "90 0 0")])).abs_diff_eq(-DVec3::Z, crate::EPSILON_64));
+        assert!(forward(&entity(&[("angles", "0 180 0"), ("angle", "90")]))
+            .abs_diff_eq(-DVec3::X, crate::EPSILON_64));
+        assert!(forward(&entity(&[("mangle", "30 90 0")]))
+            .abs_diff_eq(DVec3::new(0.0, 0.75_f64.sqrt(), -0.5), crate::EPSILON_64));
+
+        // yaw pitch roll for lights, positive pitch looks up
+        assert!(forward(&entity(&[
+            ("classname", "light_spot"),
+            ("mangle", "90 30 0")
+        ]))
+        .abs_diff_eq(DVec3::new(0.0, 0.75_f64.sqrt(), 0.5), crate::EPSILON_64));
+    }
+
+    #[test]
+    fn test_fold_into_worldspawn() {
+        let mut map = test_utils::get_map();
+
+        let mut group = entity(&[("classname", "func_group")]);
+        group.brushes = test_utils::get_map().entities.remove(0).brushes;
+
+        map.entities.push(group);
+        map.entities.push(entity(&[("classname", "func_detail")]));
+        map.entities.push(entity(&[
+            ("classname", "func_group"),
+            ("_tb_type", "_tb_layer"),
+            ("_tb_id", "1"),
+        ]));
+        map.entities.push(entity(&[("classname", "info_null")]));
+
+        let mut detail = entity(&[("classname", "func_detail"), ("_tb_layer", "1")]);
+        detail.brushes = test_utils::get_map().entities.remove(0).brushes;
+        map.entities.push(detail);
+
+        let sources = map.fold_into_worldspawn(&["func_group", "func_detail"]);
+
+        assert_eq!(map.entities.len(), 3);
+        assert_eq!(map.entities[0].brushes.len(), 2);
+        assert_eq!(map.entities[2].classname(), Some("info_null"));
+
+        // Brushes in a layer stay in it
+        assert!(map.entities[1].tb_group(1).is_some());
+        assert_eq!(map.entities[1].brushes.len(), 1);
+
+        assert_eq!(
+            sources,
+            vec![vec![(0, 0), (1, 0)], vec![(5, 0)], Vec::new()]
+        );
+    }
+
+    #[test]
+    fn test_map_round_trip() {
+        for text in [test_utils::TEST_MAP, include_str!("../../assets/test.map")] {
+            let map = parse(text);
+            let serialized = map.serialize();
+
+            assert_eq!(parse(&serialized), map);
+            assert_eq!(parse(&serialized).serialize(), serialized);
+        }
+    }
+
+    #[test]
+    fn test_entity_serialize() {
+        let mut entity = entity(&[
+            ("message", "A \"quoted\" \\ string"),
+            ("classname", "info_null"),
+            ("angle", "90"),
+        ]);
+        entity.brushes = test_utils::get_map().entities.remove(0).brushes;
+
+        let serialized = entity.serialize();
+
+        assert!(serialized.starts_with(
+            "{\n\
+            \"classname\" \"info_null\"\n\
+            \"angle\" \"90\"\n\
+            \"message\" \"A \\\"quoted\\\" \\\\ string\"\n\
+            // brush 0\n\
+            {\n\
+            ( -16 -64 -16 ) ( -16 -63 -16 ) ( -16 -64 -15 ) map/grass [ 0 -1 0 0 ] [ 0 0 -1 0 ] 0 1 1\n"
+        ));
+
+        assert_eq!(
+            parse(&serialized).entities[0].properties["message"],
+            "A \"quoted\" \\ string"
+        );
     }
 }
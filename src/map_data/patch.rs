@@ -0,0 +1,188 @@
+use glam::{DVec2, DVec3};
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct PatchVertex {
+    pub position: DVec3,
+    /// In texture space (i.e. 1.0 is one repetition of the texture)
+    pub uv: DVec2,
+}
+
+/// A Quake 3 `patchDef2` biquadratic Bezier patch.
+/// Vertices are indexed as `[y * width + x]`, where each parenthesized row in the .map is one `x` column.
+#[derive(PartialEq, Debug)]
+pub struct Patch {
+    pub texture: String,
+    pub width: usize,
+    pub height: usize,
+    pub control_points: Vec<PatchVertex>,
+}
+
+impl Patch {
+    /// Patches are made of 3x3 sections sharing their edges, so both dimensions must be odd
+    pub fn is_valid(&self) -> bool {
+        self.width >= 3
+            && self.height >= 3
+            && self.width % 2 == 1
+            && self.height % 2 == 1
+            && self.control_points.len() == self.width * self.height
+    }
+
+    /// Evaluates the patch at `subdivisions` steps along each direction of each 3x3 section.
+    /// The result uses the same indexing as `control_points`.
+    pub fn tessellate(&self, subdivisions: usize) -> Option<Patch> {
+        if !self.is_valid() || subdivisions == 0 {
+            return None;
+        }
+
+        let width = (self.width - 1) / 2 * subdivisions + 1;
+        let height = (self.height - 1) / 2 * subdivisions + 1;
+
+        // The patch is a tensor product, so each direction can be evaluated separately
+        let mut columns = Vec::with_capacity(width * self.height);
+
+        for y in 0..self.height {
+            for x in 0..width {
+                columns.push(evaluate(x, subdivisions, |i| {
+                    self.control_points[y * self.width + i]
+                }));
+            }
+        }
+
+        let mut vertices = Vec::with_capacity(width * height);
+
+        for y in 0..height {
+            for x in 0..width {
+                vertices.push(evaluate(y, subdivisions, |i| columns[i * width + x]));
+            }
+        }
+
+        Some(Patch {
+            texture: self.texture.clone(),
+            width,
+            height,
+            control_points: vertices,
+        })
+    }
+
+    pub fn serialize(&self) -> String {
+        let mut output = format!(
+            "{{\npatchDef2\n{{\n{}\n( {} {} 0 0 0 )\n(\n",
+            self.texture, self.width, self.height
+        );
+
+        for x in 0..self.width {
+            output.push('(');
+
+            for y in 0..self.height {
+                let vertex = &self.control_points[y * self.width + x];
+
+                output.push_str(&format!(
+                    " ( {} {} {} {} {} )",
+                    vertex.position.x,
+                    vertex.position.y,
+                    vertex.position.z,
+                    vertex.uv.x,
+                    vertex.uv.y
+                ));
+            }
+
+            output.push_str(" )\n");
+        }
+
+        output.push_str(")\n}\n}");
+
+        output
+    }
+}
+
+/// Evaluates output vertex `idx` along one direction,
+/// where `control_point` retrieves control points along that direction
+fn evaluate(
+    idx: usize,
+    subdivisions: usize,
+    control_point: impl Fn(usize) -> PatchVertex,
+) -> PatchVertex {
+    // The last vertex belongs to the end of the last section
+    let section = (idx / subdivisions).min((idx.max(1) - 1) / subdivisions);
+    let t = (idx - section * subdivisions) as f64 / subdivisions as f64;
+
+    let weights = [(1.0 - t) * (1.0 - t), 2.0 * t * (1.0 - t), t * t];
+
+    let mut position = DVec3::ZERO;
+    let mut uv = DVec2::ZERO;
+
+    for (i, weight) in weights.iter().enumerate() {
+        let point = control_point(section * 2 + i);
+
+        position += point.position * *weight;
+        uv += point.uv * *weight;
+    }
+
+    PatchVertex { position, uv }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Patch, PatchVertex};
+    use glam::{DVec2, DVec3};
+
+    /// A 3x5 patch bending up along Y
+    fn get_patch() -> Patch {
+        let mut control_points = Vec::new();
+
+        for y in 0..5 {
+            for x in 0..3 {
+                let z = if y == 1 || y == 3 { 16.0 } else { 0.0 };
+
+                control_points.push(PatchVertex {
+                    position: DVec3::new(x as f64 * 8.0, y as f64 * 8.0, z),
+                    uv: DVec2::new(x as f64 * 0.5, y as f64 * 0.5),
+                });
+            }
+        }
+
+        Patch {
+            texture: "TEXTURE".to_string(),
+            width: 3,
+            height: 5,
+            control_points,
+        }
+    }
+
+    #[test]
+    fn test_is_valid() {
+        let mut patch = get_patch();
+        assert!(patch.is_valid());
+
+        patch.width = 4;
+        assert!(!patch.is_valid());
+    }
+
+    #[test]
+    fn test_tessellate() {
+        let patch = get_patch();
+        let tessellated = patch.tessellate(4).expect("failed to tessellate");
+
+        assert_eq!((tessellated.width, tessellated.height), (5, 9));
+        assert_eq!(tessellated.control_points.len(), 5 * 9);
+
+        // Corners and section edges lie on the control points
+        for (x, y, control_x, control_y) in [(0, 0, 0, 0), (4, 8, 2, 4), (4, 4, 2, 2)] {
+            assert_eq!(
+                tessellated.control_points[y * 5 + x],
+                patch.control_points[control_y * 3 + control_x]
+            );
+        }
+
+        // Midpoint of the first section: 0.25 * 0 + 0.5 * 16 + 0.25 * 0
+        let middle = tessellated.control_points[2 * 5 + 2];
+        assert!(middle
+            .position
+            .abs_diff_eq(DVec3::new(8.0, 8.0, 8.0), crate::EPSILON_64));
+        assert!(middle
+            .uv
+            .abs_diff_eq(DVec2::new(0.5, 0.5), crate::EPSILON_64));
+
+        assert!(patch.tessellate(0).is_none());
+    }
+}
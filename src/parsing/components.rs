@@ -2,19 +2,21 @@
 
 use std::collections::HashMap;
 
-use glam::DVec3;
+use glam::{DVec2, DVec3};
 use nom::{
-    character::complete::{char, multispace0, multispace1},
-    combinator::map,
+    branch::alt,
+    bytes::complete::tag,
+    character::complete::{char, i32 as int, multispace0, multispace1},
+    combinator::{cut, map, map_opt, opt, peek},
     error::{context, ContextError, ParseError},
     multi::{count, many0, many1},
     number::complete::{double, float},
-    sequence::{delimited, pair, preceded, terminated, tuple},
+    sequence::{delimited, pair, preceded, separated_pair, terminated, tuple},
     IResult,
 };
 
 use super::util::{escaped_string, generic_list, identifier, ignored};
-use crate::map_data::{Brush, BrushFace, Entity, UvAxis};
+use crate::map_data::{Brush, BrushFace, Entity, Patch, PatchVertex, SurfaceAttributes, UvAxis};
 
 /// Matches a 3D coordinate in ( x y z ) form, as used in brushes
 fn point<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
@@ -45,33 +47,100 @@ fn uv_axis<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
     )(i)
 }
 
-/// Matches a brush face definition (one line in a (normal) brush, although they can technically all be in one line)
-fn brush_face<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+/// Texture alignment, which differs between map formats
+enum FaceUv {
+    /// Valve 220: [ ux uy uz uoffset ] [ vx vy vz voffset ]
+    Axes(UvAxis, UvAxis),
+    /// Standard: xoffset yoffset
+    Offsets(f64, f64),
+}
+
+fn face_uv<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
     i: &'a str,
-) -> IResult<&'a str, BrushFace, E> {
+) -> IResult<&'a str, FaceUv, E> {
     context(
-        "brush_face",
+        "face_uv",
+        alt((
+            map(pair(terminated(uv_axis, multispace0), uv_axis), |(u, v)| {
+                FaceUv::Axes(u, v)
+            }),
+            map(
+                separated_pair(double, multispace1, double),
+                |(x_offset, y_offset)| FaceUv::Offsets(x_offset, y_offset),
+            ),
+        )),
+    )(i)
+}
+
+/// Matches the `content surface value` attributes which Quake 2 and Quake 3 formats append to faces
+fn surface_attributes<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    i: &'a str,
+) -> IResult<&'a str, SurfaceAttributes, E> {
+    context(
+        "surface_attributes",
         map(
             tuple((
-                count(terminated(point, multispace0), 3),
-                terminated(identifier, multispace1),
-                count(terminated(uv_axis, multispace0), 2),
-                terminated(float, multispace0),
-                terminated(float, multispace0),
+                terminated(int, multispace1),
+                terminated(int, multispace1),
                 float,
             )),
+            |(content_flags, surface_flags, surface_value)| SurfaceAttributes {
+                content_flags,
+                surface_flags,
+                surface_value,
+            },
+        ),
+    )(i)
+}
+
+/// Matches a brush face definition (one line in a (normal) brush, although they can technically all be in one line).
+/// Both Valve 220 and Standard texture alignment are accepted, optionally followed by Quake 2/Quake 3 surface attributes.
+fn brush_face<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    i: &'a str,
+) -> IResult<&'a str, BrushFace, E> {
+    context(
+        "brush_face",
+        map(
+            // Anything starting with a point must be a face
+            preceded(
+                peek(char('(')),
+                cut(tuple((
+                    count(terminated(point, multispace0), 3),
+                    terminated(identifier, multispace1),
+                    terminated(face_uv, multispace0),
+                    terminated(float, multispace0),
+                    terminated(float, multispace0),
+                    float,
+                    opt(preceded(multispace1, surface_attributes)),
+                ))),
+            ),
             |t| {
-                let (points, texture, mut uv_axes, rotation, x_scale, y_scale) = t;
+                let (points, texture, uv, rotation, x_scale, y_scale, surface) = t;
+                let points = points.try_into().unwrap();
 
-                BrushFace::new(
-                    points.try_into().unwrap(),
-                    texture.to_string(),
-                    uv_axes.remove(0),
-                    uv_axes.remove(0),
-                    rotation,
-                    x_scale,
-                    y_scale,
-                )
+                let mut face = match uv {
+                    FaceUv::Axes(u, v) => BrushFace::new(
+                        points,
+                        texture.to_string(),
+                        u,
+                        v,
+                        rotation,
+                        x_scale,
+                        y_scale,
+                    ),
+                    FaceUv::Offsets(x_offset, y_offset) => BrushFace::new_standard(
+                        points,
+                        texture.to_string(),
+                        x_offset,
+                        y_offset,
+                        rotation,
+                        x_scale,
+                        y_scale,
+                    ),
+                };
+
+                face.surface = surface;
+                face
             },
         ),
     )(i)
@@ -83,43 +152,143 @@ fn brush<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
     context(
         "brush",
         map(
-            delimited(
+            preceded(
                 char('{'),
-                many1(delimited(ignored, brush_face, ignored)),
-                char('}'),
+                cut(terminated(
+                    many1(delimited(ignored, brush_face, ignored)),
+                    char('}'),
+                )),
             ),
             |v| Brush { faces: v },
         ),
     )(i)
 }
 
+fn patch_vertex<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    i: &'a str,
+) -> IResult<&'a str, PatchVertex, E> {
+    context(
+        "patch_vertex",
+        map(
+            delimited(char('('), generic_list(5, double), char(')')),
+            |v| PatchVertex {
+                position: DVec3::new(v[0], v[1], v[2]),
+                uv: DVec2::new(v[3], v[4]),
+            },
+        ),
+    )(i)
+}
+
+/// Matches a Quake 3 `patchDef2` block, which appears alongside brushes in an entity
+fn patch<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+    i: &'a str,
+) -> IResult<&'a str, Patch, E> {
+    context(
+        "patch",
+        preceded(
+            tuple((char('{'), ignored, tag("patchDef2"))),
+            cut(map_opt(
+                delimited(
+                    tuple((ignored, char('{'), ignored)),
+                    tuple((
+                        terminated(identifier, ignored),
+                        terminated(
+                            delimited(char('('), generic_list(5, double), char(')')),
+                            ignored,
+                        ),
+                        delimited(
+                            terminated(char('('), ignored),
+                            many1(terminated(
+                                delimited(
+                                    terminated(char('('), ignored),
+                                    many1(terminated(patch_vertex, ignored)),
+                                    char(')'),
+                                ),
+                                ignored,
+                            )),
+                            char(')'),
+                        ),
+                    )),
+                    tuple((ignored, char('}'), ignored, char('}'))),
+                ),
+                |(texture, info, columns)| {
+                    let width = columns.len();
+                    let height = columns[0].len();
+
+                    if info[0] as usize != width || columns.iter().any(|c| c.len() != height) {
+                        return None;
+                    }
+
+                    // Transpose the columns into rows
+                    let control_points = (0..height)
+                        .flat_map(|y| columns.iter().map(move |column| column[y]))
+                        .collect();
+
+                    let patch = Patch {
+                        texture: texture.to_string(),
+                        width,
+                        height,
+                        control_points,
+                    };
+
+                    Some(patch).filter(|p| p.is_valid() && info[1] as usize == height)
+                },
+            )),
+        ),
+    )(i)
+}
+
+enum EntityChild {
+    Brush(Brush),
+    Patch(Patch),
+}
+
 pub fn entity<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
     i: &'a str,
 ) -> IResult<&'a str, Entity, E> {
     context(
         "entity",
         map(
-            delimited(
+            preceded(
                 terminated(char('{'), ignored),
-                tuple((
-                    many1(terminated(
-                        pair(escaped_string, preceded(ignored, escaped_string)),
-                        ignored,
+                cut(terminated(
+                    tuple((
+                        many1(terminated(
+                            pair(escaped_string, preceded(ignored, escaped_string)),
+                            ignored,
+                        )),
+                        many0(terminated(
+                            alt((
+                                map(patch, EntityChild::Patch),
+                                map(brush, EntityChild::Brush),
+                            )),
+                            ignored,
+                        )),
                     )),
-                    many0(terminated(brush, ignored)),
+                    preceded(ignored, char('}')),
                 )),
-                preceded(ignored, char('}')),
             ),
-            |(prop_tuples, brushes)| {
+            |(prop_tuples, children)| {
                 let mut properties = HashMap::new();
 
                 for (key, value) in prop_tuples {
                     properties.insert(key, value);
                 }
 
+                let mut brushes = Vec::new();
+                let mut patches = Vec::new();
+
+                for child in children {
+                    match child {
+                        EntityChild::Brush(brush) => brushes.push(brush),
+                        EntityChild::Patch(patch) => patches.push(patch),
+                    }
+                }
+
                 Entity {
                     properties,
                     brushes,
+                    patches,
                 }
             },
         ),
@@ -128,9 +297,9 @@ pub fn entity<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
 
 #[cfg(test)]
 mod tests {
-    use super::{brush, brush_face, entity, point, uv_axis};
-    use crate::map_data::{Brush, BrushFace, Entity, UvAxis};
-    use glam::DVec3;
+    use super::{brush, brush_face, entity, patch, point, uv_axis};
+    use crate::map_data::{Brush, BrushFace, Entity, SurfaceAttributes, UvAxis};
+    use glam::{DVec2, DVec3};
     use std::collections::HashMap;
 
     fn test_brush_face(i: f32) -> BrushFace {
@@ -190,6 +359,52 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_parse_standard_brush_face() {
+        assert_eq!(
+            brush_face::<()>("( 0 0 0 ) ( 1 0 0 ) ( 0 1 0 ) TEXTURE 16 -8.5 90 0.5 2 asdf"),
+            Ok((
+                " asdf",
+                BrushFace::new(
+                    [
+                        DVec3::new(0.0, 0.0, 0.0),
+                        DVec3::new(1.0, 0.0, 0.0),
+                        DVec3::new(0.0, 1.0, 0.0),
+                    ],
+                    "TEXTURE".to_string(),
+                    UvAxis {
+                        axis: DVec3::new(0.0, 1.0, 0.0),
+                        offset: 16.0,
+                    },
+                    UvAxis {
+                        axis: DVec3::new(1.0, 0.0, 0.0),
+                        offset: -8.5,
+                    },
+                    90.0,
+                    0.5,
+                    2.0,
+                )
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_quake2_brush_face() {
+        let mut face = test_brush_face(0.0);
+        face.surface = Some(SurfaceAttributes {
+            content_flags: 1,
+            surface_flags: -2,
+            surface_value: 300.0,
+        });
+
+        assert_eq!(
+            brush_face::<()>(
+                "( 0 1 2 ) ( 3 4 5 ) ( 6 7 8 ) TEXTURE [ 9 10 11 12 ] [ 13 14 15 16 ] 17 18 19 1 -2 300\n( 0"
+            ),
+            Ok(("\n( 0", face))
+        );
+    }
+
     #[test]
     fn test_parse_brush() {
         assert_eq!(
@@ -229,9 +444,44 @@ mod tests {
                     properties,
                     brushes: vec![Brush {
                         faces: vec![test_brush_face(0.0)]
-                    }]
+                    }],
+                    patches: Vec::new(),
                 }
             ))
         );
     }
+
+    #[test]
+    fn test_parse_patch() {
+        let (rest, parsed) = patch::<nom::error::VerboseError<&str>>(
+            "{
+                patchDef2
+                {
+                common/caulk
+                ( 3 3 0 0 0 )
+                (
+                ( ( 0 0 0 0 0 ) ( 0 8 0 0 0.5 ) ( 0 16 0 0 1 ) )
+                ( ( 8 0 0 0.5 0 ) ( 8 8 8 0.5 0.5 ) ( 8 16 0 0.5 1 ) )
+                ( ( 16 0 0 1 0 ) ( 16 8 0 1 0.5 ) ( 16 16 0 1 1 ) )
+                )
+                }
+                } asdf",
+        )
+        .expect("failed to parse");
+
+        assert_eq!(rest, " asdf");
+        assert_eq!(parsed.texture, "common/caulk");
+        assert_eq!((parsed.width, parsed.height), (3, 3));
+
+        // Columns in the file are indexed by x
+        assert_eq!(parsed.control_points[1].position, DVec3::new(8.0, 0.0, 0.0));
+        assert_eq!(parsed.control_points[4].position, DVec3::new(8.0, 8.0, 8.0));
+        assert_eq!(parsed.control_points[3].uv, DVec2::new(0.0, 0.5));
+
+        // Dimensions must match the header
+        assert!(patch::<()>(
+            "{ patchDef2 { tex ( 3 5 0 0 0 ) ( ( (0 0 0 0 0) (0 0 0 0 0) (0 0 0 0 0) ) ) } }"
+        )
+        .is_err());
+    }
 }
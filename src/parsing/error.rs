@@ -0,0 +1,96 @@
+use nom::{
+    error::{VerboseError, VerboseErrorKind},
+    Offset,
+};
+use std::fmt;
+use thiserror::Error;
+
+/// A parse failure, located in the source text
+#[derive(Error, Debug, Clone, PartialEq)]
+pub struct MapParseError {
+    /// 1-based line number
+    pub line: usize,
+    /// 1-based column number, in characters
+    pub column: usize,
+    /// The parsers which were active at the failure, outermost first (e.g. `["map", "entity", "brush"]`)
+    pub context: Vec<&'static str>,
+    /// What the innermost parser expected
+    pub message: String,
+    /// The failing line, with a caret marking the column
+    pub snippet: String,
+}
+
+impl MapParseError {
+    pub fn new(input: &str, error: VerboseError<&str>) -> Self {
+        // The first error is the innermost one
+        let position = error
+            .errors
+            .first()
+            .map(|(i, _)| input.offset(i))
+            .unwrap_or_default();
+
+        let line_start = input[..position].rfind('\n').map_or(0, |idx| idx + 1);
+        let line_end = input[position..]
+            .find('\n')
+            .map_or(input.len(), |idx| position + idx);
+
+        let line = input[..position].matches('\n').count() + 1;
+        let prefix = &input[line_start..position];
+        let column = prefix.chars().count() + 1;
+
+        // Keep tabs so the caret lines up
+        let caret_indent = prefix
+            .chars()
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect::<String>();
+
+        let snippet = format!(
+            "{}\n{}^",
+            input[line_start..line_end].trim_end(),
+            caret_indent
+        );
+
+        let message = match error.errors.first().map(|(_, kind)| kind) {
+            Some(VerboseErrorKind::Char(c)) => format!("expected '{}'", c),
+            Some(VerboseErrorKind::Nom(kind)) => {
+                format!("unexpected input ({})", kind.description())
+            }
+            Some(VerboseErrorKind::Context(ctx)) => format!("invalid {}", ctx),
+            None => "unknown error".to_string(),
+        };
+
+        let context = error
+            .errors
+            .iter()
+            .rev()
+            .filter_map(|(_, kind)| match kind {
+                VerboseErrorKind::Context(ctx) => Some(*ctx),
+                _ => None,
+            })
+            .collect();
+
+        Self {
+            line,
+            column,
+            context,
+            message,
+            snippet,
+        }
+    }
+}
+
+impl fmt::Display for MapParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at line {}, column {}",
+            self.message, self.line, self.column
+        )?;
+
+        if !self.context.is_empty() {
+            write!(f, " (in {})", self.context.join(" > "))?;
+        }
+
+        write!(f, "\n{}", self.snippet)
+    }
+}
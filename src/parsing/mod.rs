@@ -1,12 +1,12 @@
-//! A parser for Quake .map files, targeted at Valve format.
+//! A parser for Quake .map files, targeted at Valve format (Standard format faces are also accepted).
 //! It aims to be compatible with (i.e. as lenient as) TrenchBroom in what kind of inputs it accepts.
 
 use nom::{
-    combinator::map,
-    error::{context, ContextError, ParseError},
+    combinator::{all_consuming, map},
+    error::{context, ContextError, ParseError, VerboseError},
     multi::many1,
     sequence::{delimited, terminated},
-    IResult,
+    Finish, IResult,
 };
 
 use crate::map_data::Map;
@@ -14,7 +14,10 @@ use crate::map_data::Map;
 pub mod components;
 use components::entity;
 
-mod util;
+mod error;
+pub use error::*;
+
+pub(crate) mod util;
 use util::ignored;
 
 pub fn parse_map<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
@@ -22,20 +25,54 @@ pub fn parse_map<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
 ) -> IResult<&'a str, Map, E> {
     context(
         "map",
-        map(
+        all_consuming(map(
             delimited(ignored, many1(terminated(entity, ignored)), ignored),
             |entities| Map { entities },
-        ),
+        )),
     )(i)
 }
 
+/// Parses a whole map, reporting where and why parsing failed if it does
+pub fn parse_map_verbose(i: &str) -> Result<Map, MapParseError> {
+    parse_map::<VerboseError<&str>>(i)
+        .finish()
+        .map(|(_, map)| map)
+        .map_err(|error| MapParseError::new(i, error))
+}
+
 #[cfg(test)]
 mod tests {
-    use super::parse_map;
+    use super::{parse_map, parse_map_verbose};
     use crate::test_utils;
 
     #[test]
     fn test_parse_map() {
         assert!(parse_map::<()>(test_utils::TEST_MAP).is_ok());
     }
+
+    #[test]
+    fn test_parse_map_error() {
+        let malformed =
+            test_utils::TEST_MAP.replace("map/dirt [ -1 0 0 0 ]", "map/dirt [ -1 0 0 ]");
+        let error = parse_map_verbose(&malformed).expect_err("parsed malformed map");
+
+        assert_eq!(error.line, 14);
+        assert_eq!(error.column, 49);
+        assert_eq!(
+            error.context,
+            vec!["map", "entity", "brush", "brush_face", "face_uv"]
+        );
+        assert_eq!(
+            error.snippet,
+            format!(
+                "( 64 16 16 ) ( 65 16 16 ) ( 64 16 17 ) map/dirt [ -1 0 0 ] [ 0 0 -1 0 ] 0 1 1\n{}^",
+                " ".repeat(48)
+            )
+        );
+
+        let error = parse_map_verbose("{ \"classname\" \"worldspawn\" }\n}")
+            .expect_err("parsed trailing garbage");
+
+        assert_eq!((error.line, error.column), (2, 1));
+    }
 }
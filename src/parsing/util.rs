@@ -18,7 +18,7 @@ pub fn identifier<'a, E: ParseError<&'a str>>(i: &'a str) -> IResult<&'a str, &'
 
 /// Matches a comment and discards it
 pub fn comment<'a, E: ParseError<&'a str>>(i: &'a str) -> IResult<&'a str, (), E> {
-    value((), pair(tag("//"), is_not("\r\n")))(i)
+    value((), pair(tag("//"), take_till(|c| c == '\r' || c == '\n')))(i)
 }
 
 /// Matches any whitespace or comment and discards it
@@ -81,6 +81,7 @@ mod tests {
     #[test]
     fn test_parse_comment() {
         assert_eq!(comment::<()>("// this is a test"), Ok(("", ())));
+        assert_eq!(comment::<()>("//\nasdf"), Ok(("\nasdf", ())));
     }
 
     #[test]
@@ -1,9 +1,10 @@
 #![cfg(test)]
 
 use crate::{
-    map_data::{Brush, Entity, Map},
+    map_data::{Brush, BrushFace, Entity, Map, UvAxis},
     parsing::parse_map,
 };
+use glam::DVec3;
 
 pub const TEST_MAP: &str = r#"// Game: Fumohouse
 // Format: Valve
@@ -23,7 +24,7 @@ pub const TEST_MAP: &str = r#"// Game: Fumohouse
 }
 }"#;
 
-fn get_map() -> Map {
+pub fn get_map() -> Map {
     parse_map::<()>(TEST_MAP).expect("failed to parse").1
 }
 
@@ -34,3 +35,39 @@ fn get_entity() -> Entity {
 pub fn get_brush() -> Brush {
     get_entity().brushes.remove(0)
 }
+
+/// A face on the plane `normal · p = dist`
+pub fn plane_face(normal: DVec3, dist: f64) -> BrushFace {
+    let normal = normal.normalize();
+    let u = normal.any_orthonormal_vector();
+    let v = normal.cross(u);
+    let origin = normal * dist;
+
+    let axis = |axis| UvAxis { axis, offset: 0.0 };
+
+    BrushFace::new(
+        [origin, origin + v, origin + u],
+        "wall".to_string(),
+        axis(u),
+        axis(v),
+        0.0,
+        1.0,
+        1.0,
+    )
+}
+
+/// A cylinder with a radius of 64 and a height of 64, centered on the origin.
+/// The caps are the last two faces.
+pub fn get_cylinder(sides: usize) -> Brush {
+    let mut faces = (0..sides)
+        .map(|i| {
+            let angle = i as f64 / sides as f64 * std::f64::consts::TAU;
+            plane_face(DVec3::new(angle.cos(), angle.sin(), 0.0), 64.0)
+        })
+        .collect::<Vec<_>>();
+
+    faces.push(plane_face(DVec3::Z, 32.0));
+    faces.push(plane_face(-DVec3::Z, 32.0));
+
+    Brush { faces }
+}
@@ -0,0 +1,44 @@
+use super::{decode_indexed, DecodedTexture, Palette, TextureFormatError};
+use crate::binary::{read_bytes, read_u32};
+
+/// Decodes a Quake `.lmp` picture (`qpic_t`), where index 255 is transparent.
+/// Raw lumps without a header (such as `palette.lmp` and `colormap.lmp`) are not pictures.
+pub fn decode_lmp(data: &[u8], palette: &Palette) -> Result<DecodedTexture, TextureFormatError> {
+    let width = read_u32(data, 0)?;
+    let height = read_u32(data, 4)?;
+
+    let pixels = (width as usize)
+        .checked_mul(height as usize)
+        .filter(|pixels| *pixels > 0 && data.len() == 8 + pixels)
+        .ok_or(TextureFormatError::InvalidDimensions(width, height))?;
+
+    let indices = read_bytes(data, 8, pixels)?;
+
+    Ok(decode_indexed(width, height, indices, palette, Some(255)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::decode_lmp;
+    use crate::texture_formats::{Palette, TextureFormatError};
+
+    #[test]
+    fn test_decode_lmp() {
+        let palette = Palette::from_bytes(&[[10, 20, 30]; 256].concat()).unwrap();
+
+        let mut data = Vec::new();
+        data.extend(2u32.to_le_bytes());
+        data.extend(1u32.to_le_bytes());
+        data.extend([0, 255]);
+
+        let texture = decode_lmp(&data, &palette).expect("failed to decode");
+        assert_eq!((texture.width, texture.height), (2, 1));
+        assert_eq!(texture.data, vec![10, 20, 30, 255, 0, 0, 0, 0]);
+
+        data.push(0);
+        assert_eq!(
+            decode_lmp(&data, &palette),
+            Err(TextureFormatError::InvalidDimensions(2, 1))
+        );
+    }
+}
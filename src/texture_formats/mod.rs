@@ -0,0 +1,104 @@
+//! Decoders for the palette-indexed texture formats used by Quake-family games.
+//! References: https://www.gamers.org/dEngine/quake/spec/quake-spec34/qkspec_7.htm
+//! and https://developer.valvesoftware.com/wiki/WAD
+
+use crate::binary::UnexpectedEof;
+use thiserror::Error;
+
+mod lmp;
+pub use lmp::*;
+
+mod palette;
+pub use palette::*;
+
+mod wal;
+pub use wal::*;
+
+mod wad;
+pub use wad::*;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum TextureFormatError {
+    #[error("unexpected end of data")]
+    UnexpectedEof,
+    #[error("unrecognized file identifier")]
+    InvalidMagic,
+    #[error("invalid texture dimensions {0}x{1}")]
+    InvalidDimensions(u32, u32),
+    #[error("texture data is stored outside of the texture")]
+    ExternalData,
+    #[error("compressed lumps are not supported")]
+    Compressed,
+    #[error("no palette available")]
+    MissingPalette,
+}
+
+/// Standalone palette-indexed image files
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum IndexedImageFormat {
+    Lmp,
+    Wal,
+}
+
+impl IndexedImageFormat {
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        if extension.eq_ignore_ascii_case("lmp") {
+            Some(IndexedImageFormat::Lmp)
+        } else if extension.eq_ignore_ascii_case("wal") {
+            Some(IndexedImageFormat::Wal)
+        } else {
+            None
+        }
+    }
+
+    pub fn decode(
+        self,
+        data: &[u8],
+        palette: &Palette,
+    ) -> Result<DecodedTexture, TextureFormatError> {
+        match self {
+            IndexedImageFormat::Lmp => decode_lmp(data, palette),
+            IndexedImageFormat::Wal => decode_wal(data, palette),
+        }
+    }
+}
+
+/// A decoded texture, as 8-bit sRGB RGBA pixels in row order
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct DecodedTexture {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+impl From<UnexpectedEof> for TextureFormatError {
+    fn from(_: UnexpectedEof) -> Self {
+        TextureFormatError::UnexpectedEof
+    }
+}
+
+/// Converts palette indices to RGBA, where `transparent_index` (if any) is fully transparent
+fn decode_indexed(
+    width: u32,
+    height: u32,
+    indices: &[u8],
+    palette: &Palette,
+    transparent_index: Option<u8>,
+) -> DecodedTexture {
+    let mut data = Vec::with_capacity(indices.len() * 4);
+
+    for index in indices {
+        if Some(*index) == transparent_index {
+            data.extend([0, 0, 0, 0]);
+        } else {
+            data.extend(palette.color(*index));
+            data.push(255);
+        }
+    }
+
+    DecodedTexture {
+        width,
+        height,
+        data,
+    }
+}
@@ -0,0 +1,90 @@
+use super::TextureFormatError;
+
+const PALETTE_SIZE: usize = 256 * 3;
+const PCX_MANUFACTURER: u8 = 0x0a;
+const PCX_PALETTE_MARKER: u8 = 0x0c;
+
+/// A 256-color RGB palette, such as Quake's `gfx/palette.lmp`
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Palette {
+    colors: Vec<[u8; 3]>,
+}
+
+impl Palette {
+    /// Reads a palette from 256 consecutive RGB triples. Any trailing data is ignored.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, TextureFormatError> {
+        if data.len() < PALETTE_SIZE {
+            return Err(TextureFormatError::UnexpectedEof);
+        }
+
+        Ok(Self::from_rgb(&data[..PALETTE_SIZE]))
+    }
+
+    /// Reads the palette at the end of an 8-bit PCX image, such as Quake 2's `pics/colormap.pcx`
+    pub fn from_pcx(data: &[u8]) -> Result<Self, TextureFormatError> {
+        if data.first() != Some(&PCX_MANUFACTURER) {
+            return Err(TextureFormatError::InvalidMagic);
+        }
+
+        let start = data
+            .len()
+            .checked_sub(PALETTE_SIZE + 1)
+            .ok_or(TextureFormatError::UnexpectedEof)?;
+
+        if data[start] != PCX_PALETTE_MARKER {
+            return Err(TextureFormatError::MissingPalette);
+        }
+
+        Self::from_bytes(&data[start + 1..])
+    }
+
+    /// Reads a palette file, which is either a PCX image or raw colors (e.g. `gfx/palette.lmp`)
+    pub fn from_file(extension: Option<&str>, data: &[u8]) -> Result<Self, TextureFormatError> {
+        match extension {
+            Some(ext) if ext.eq_ignore_ascii_case("pcx") => Self::from_pcx(data),
+            _ => Self::from_bytes(data),
+        }
+    }
+
+    /// Reads a palette with up to 256 colors. Missing colors are black.
+    pub(crate) fn from_rgb(data: &[u8]) -> Self {
+        let mut colors = data
+            .chunks_exact(3)
+            .take(256)
+            .map(|c| [c[0], c[1], c[2]])
+            .collect::<Vec<_>>();
+
+        colors.resize(256, [0, 0, 0]);
+
+        Self { colors }
+    }
+
+    pub fn color(&self, index: u8) -> [u8; 3] {
+        self.colors[index as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Palette;
+    use crate::texture_formats::TextureFormatError;
+
+    #[test]
+    fn test_from_pcx() {
+        let colors = (0..=255u8).flat_map(|i| [i, 0, 0]).collect::<Vec<_>>();
+
+        let mut data = vec![0x0a];
+        data.extend([0; 127]);
+        data.push(0x0c);
+        data.extend(&colors);
+
+        let palette = Palette::from_file(Some("PCX"), &data).expect("failed to read palette");
+        assert_eq!(palette, Palette::from_bytes(&colors).unwrap());
+        assert_eq!(palette.color(200), [200, 0, 0]);
+
+        assert_eq!(
+            Palette::from_pcx(&colors),
+            Err(TextureFormatError::InvalidMagic)
+        );
+    }
+}
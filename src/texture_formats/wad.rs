@@ -0,0 +1,323 @@
+use super::{decode_indexed, DecodedTexture, Palette, TextureFormatError};
+use crate::binary::{read_bytes, read_name, read_u16, read_u32};
+
+const LUMP_PALETTE: u8 = 0x40;
+const LUMP_MIPTEX_HL: u8 = 0x43;
+const LUMP_MIPTEX: u8 = 0x44;
+
+const DIR_ENTRY_SIZE: usize = 32;
+const NAME_SIZE: usize = 16;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WadVersion {
+    /// Quake, where textures use a shared palette
+    Wad2,
+    /// Half-Life, where each texture embeds its own palette
+    Wad3,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct WadLump {
+    pub name: String,
+    pub lump_type: u8,
+    pub compressed: bool,
+    pub data: Vec<u8>,
+}
+
+/// A parsed WAD2/WAD3 texture package
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Wad {
+    pub version: WadVersion,
+    pub lumps: Vec<WadLump>,
+}
+
+impl Wad {
+    pub fn parse(data: &[u8]) -> Result<Self, TextureFormatError> {
+        let version = match read_bytes(data, 0, 4)? {
+            b"WAD2" => WadVersion::Wad2,
+            b"WAD3" => WadVersion::Wad3,
+            _ => return Err(TextureFormatError::InvalidMagic),
+        };
+
+        let num_lumps = read_u32(data, 4)? as usize;
+        let dir_offset = read_u32(data, 8)? as usize;
+
+        let mut lumps = Vec::with_capacity(num_lumps.min(data.len() / DIR_ENTRY_SIZE));
+
+        for i in 0..num_lumps {
+            let entry = dir_offset + i * DIR_ENTRY_SIZE;
+
+            let offset = read_u32(data, entry)? as usize;
+            let disk_size = read_u32(data, entry + 4)? as usize;
+            let lump_type = read_bytes(data, entry + 12, 1)?[0];
+            let compressed = read_bytes(data, entry + 13, 1)?[0] != 0;
+            let name = read_name(data, entry + 16, NAME_SIZE)?;
+
+            lumps.push(WadLump {
+                name,
+                lump_type,
+                compressed,
+                data: read_bytes(data, offset, disk_size)?.to_vec(),
+            });
+        }
+
+        Ok(Self { version, lumps })
+    }
+
+    /// Finds a lump by name. Names are compared case-insensitively, as in the engines.
+    pub fn lump(&self, name: &str) -> Option<&WadLump> {
+        self.lumps
+            .iter()
+            .find(|lump| lump.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn has_texture(&self, name: &str) -> bool {
+        self.texture_lump(name).is_some()
+    }
+
+    fn texture_lump(&self, name: &str) -> Option<&WadLump> {
+        self.lumps
+            .iter()
+            .filter(|lump| matches!(lump.lump_type, LUMP_MIPTEX | LUMP_MIPTEX_HL))
+            .find(|lump| lump.name.eq_ignore_ascii_case(name))
+    }
+
+    /// The palette lump (`PALETTE` in Quake's `gfx.wad`), if there is one
+    pub fn palette(&self) -> Option<Palette> {
+        self.lumps
+            .iter()
+            .find(|lump| lump.lump_type == LUMP_PALETTE && !lump.compressed)
+            .and_then(|lump| Palette::from_bytes(&lump.data).ok())
+    }
+
+    /// Whether decoding textures from this WAD requires an external palette
+    pub fn needs_palette(&self) -> bool {
+        self.version == WadVersion::Wad2
+    }
+
+    /// Decodes the texture named `name`, if this WAD contains it.
+    /// `palette` is only used for WAD2, as WAD3 textures have their own.
+    pub fn texture(
+        &self,
+        name: &str,
+        palette: Option<&Palette>,
+    ) -> Option<Result<DecodedTexture, TextureFormatError>> {
+        let lump = self.texture_lump(name)?;
+
+        if lump.compressed {
+            return Some(Err(TextureFormatError::Compressed));
+        }
+
+        Some(decode_miptex(&lump.data, self.version, palette))
+    }
+}
+
+/// Decodes the largest mip level of a miptex lump
+pub fn decode_miptex(
+    data: &[u8],
+    version: WadVersion,
+    palette: Option<&Palette>,
+) -> Result<DecodedTexture, TextureFormatError> {
+    let name = read_name(data, 0, NAME_SIZE)?;
+    let width = read_u32(data, 16)?;
+    let height = read_u32(data, 20)?;
+
+    if width == 0 || height == 0 || width % 8 != 0 || height % 8 != 0 {
+        return Err(TextureFormatError::InvalidDimensions(width, height));
+    }
+
+    let mut offsets = [0; 4];
+    for (i, offset) in offsets.iter_mut().enumerate() {
+        *offset = read_u32(data, 24 + i * 4)? as usize;
+    }
+
+    if offsets[0] == 0 {
+        return Err(TextureFormatError::ExternalData);
+    }
+
+    let pixels = (width as usize)
+        .checked_mul(height as usize)
+        .ok_or(TextureFormatError::InvalidDimensions(width, height))?;
+    let indices = read_bytes(data, offsets[0], pixels)?;
+
+    let embedded_palette;
+    let palette = match version {
+        WadVersion::Wad2 => palette.ok_or(TextureFormatError::MissingPalette)?,
+        WadVersion::Wad3 => {
+            // The palette follows the smallest mip level
+            let palette_offset = offsets[3] + pixels / 64;
+            let num_colors = read_u16(data, palette_offset)? as usize;
+
+            embedded_palette =
+                Palette::from_rgb(read_bytes(data, palette_offset + 2, num_colors * 3)?);
+            &embedded_palette
+        }
+    };
+
+    // Textures starting with '{' use the last palette entry for transparency
+    let transparent_index = if name.starts_with('{') {
+        Some(255)
+    } else {
+        None
+    };
+
+    Ok(decode_indexed(
+        width,
+        height,
+        indices,
+        palette,
+        transparent_index,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_miptex, Wad, WadVersion, LUMP_MIPTEX, LUMP_MIPTEX_HL, LUMP_PALETTE};
+    use crate::texture_formats::{Palette, TextureFormatError};
+
+    /// A palette where index `i` is `(i, 255 - i, 7)`
+    fn palette_bytes() -> Vec<u8> {
+        (0..=255u8).flat_map(|i| [i, 255 - i, 7]).collect()
+    }
+
+    /// An 8x8 miptex where pixel `i` uses palette index `i`, except for the last one which uses 255
+    fn miptex(name: &str, version: WadVersion) -> Vec<u8> {
+        let mut data = Vec::new();
+
+        let mut name_bytes = name.as_bytes().to_vec();
+        name_bytes.resize(16, 0);
+        data.extend(name_bytes);
+
+        data.extend(8u32.to_le_bytes());
+        data.extend(8u32.to_le_bytes());
+
+        let sizes = [64, 16, 4, 1];
+        let mut offset = 40;
+        for size in sizes {
+            data.extend((offset as u32).to_le_bytes());
+            offset += size;
+        }
+
+        data.extend((0..63).chain([255]));
+
+        for size in &sizes[1..] {
+            data.extend((0..*size).map(|i| i as u8));
+        }
+
+        if version == WadVersion::Wad3 {
+            data.extend(256u16.to_le_bytes());
+            data.extend(palette_bytes());
+            data.extend([0, 0]);
+        }
+
+        data
+    }
+
+    fn wad(version: WadVersion, lumps: &[(&str, u8, Vec<u8>)]) -> Vec<u8> {
+        let mut data = match version {
+            WadVersion::Wad2 => b"WAD2".to_vec(),
+            WadVersion::Wad3 => b"WAD3".to_vec(),
+        };
+
+        let dir_offset = 12 + lumps.iter().map(|l| l.2.len()).sum::<usize>();
+
+        data.extend((lumps.len() as u32).to_le_bytes());
+        data.extend((dir_offset as u32).to_le_bytes());
+
+        for (_, _, lump_data) in lumps {
+            data.extend(lump_data);
+        }
+
+        let mut offset = 12;
+        for (name, lump_type, lump_data) in lumps {
+            data.extend((offset as u32).to_le_bytes());
+            data.extend((lump_data.len() as u32).to_le_bytes());
+            data.extend((lump_data.len() as u32).to_le_bytes());
+            data.extend([*lump_type, 0, 0, 0]);
+
+            let mut name_bytes = name.as_bytes().to_vec();
+            name_bytes.resize(16, 0);
+            data.extend(name_bytes);
+
+            offset += lump_data.len();
+        }
+
+        data
+    }
+
+    #[test]
+    fn test_wad2() {
+        let data = wad(
+            WadVersion::Wad2,
+            &[
+                ("palette", LUMP_PALETTE, palette_bytes()),
+                ("brick", LUMP_MIPTEX, miptex("brick", WadVersion::Wad2)),
+                ("{fence", LUMP_MIPTEX, miptex("{fence", WadVersion::Wad2)),
+            ],
+        );
+
+        let wad = Wad::parse(&data).expect("failed to parse wad");
+        assert_eq!(wad.version, WadVersion::Wad2);
+        assert_eq!(wad.lumps.len(), 3);
+        assert!(wad.needs_palette());
+
+        assert_eq!(
+            wad.texture("brick", None),
+            Some(Err(TextureFormatError::MissingPalette))
+        );
+
+        let palette = wad.palette().expect("missing palette");
+        assert_eq!(palette, Palette::from_bytes(&palette_bytes()).unwrap());
+
+        let texture = wad.texture("BRICK", Some(&palette)).unwrap().unwrap();
+        assert_eq!((texture.width, texture.height), (8, 8));
+        assert_eq!(texture.data.len(), 8 * 8 * 4);
+        assert_eq!(&texture.data[4 * 9..4 * 10], &[9, 246, 7, 255]);
+
+        assert_eq!(&texture.data[4 * 63..], &[255, 0, 7, 255]);
+
+        // Fences are transparent where index 255 is used
+        let fence = wad.texture("{fence", Some(&palette)).unwrap().unwrap();
+        assert_eq!(&fence.data[4 * 9..4 * 10], &[9, 246, 7, 255]);
+        assert_eq!(&fence.data[4 * 63..], &[0, 0, 0, 0]);
+
+        assert!(wad.has_texture("{FENCE"));
+        assert!(!wad.has_texture("palette"));
+        assert!(wad.texture("missing", Some(&palette)).is_none());
+    }
+
+    #[test]
+    fn test_wad3() {
+        let data = wad(
+            WadVersion::Wad3,
+            &[("hl_tex", LUMP_MIPTEX_HL, miptex("hl_tex", WadVersion::Wad3))],
+        );
+
+        let wad = Wad::parse(&data).expect("failed to parse wad");
+        assert_eq!(wad.version, WadVersion::Wad3);
+        assert!(!wad.needs_palette());
+
+        let texture = wad.texture("hl_tex", None).unwrap().unwrap();
+        assert_eq!(&texture.data[4 * 62..4 * 63], &[62, 193, 7, 255]);
+    }
+
+    #[test]
+    fn test_invalid_wad() {
+        assert_eq!(Wad::parse(b"PACK"), Err(TextureFormatError::InvalidMagic));
+
+        let mut data = wad(
+            WadVersion::Wad2,
+            &[("brick", LUMP_MIPTEX, miptex("brick", WadVersion::Wad2))],
+        );
+        data.truncate(data.len() - 1);
+        assert_eq!(Wad::parse(&data), Err(TextureFormatError::UnexpectedEof));
+
+        // Huge dimensions shouldn't overflow
+        let mut data = miptex("huge", WadVersion::Wad3);
+        data[16..24].copy_from_slice(&[0, 0, 1, 0, 0, 0, 1, 0]);
+        assert_eq!(
+            decode_miptex(&data, WadVersion::Wad3, None),
+            Err(TextureFormatError::UnexpectedEof)
+        );
+    }
+}
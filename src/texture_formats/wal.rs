@@ -0,0 +1,50 @@
+use super::{decode_indexed, DecodedTexture, Palette, TextureFormatError};
+use crate::binary::{read_bytes, read_u32};
+
+const NAME_SIZE: usize = 32;
+
+/// Decodes the largest mip level of a Quake 2 `.wal` texture.
+/// Quake 2 textures have no palette of their own, and normally use the one in `pics/colormap.pcx`.
+pub fn decode_wal(data: &[u8], palette: &Palette) -> Result<DecodedTexture, TextureFormatError> {
+    let width = read_u32(data, NAME_SIZE)?;
+    let height = read_u32(data, NAME_SIZE + 4)?;
+
+    if width == 0 || height == 0 {
+        return Err(TextureFormatError::InvalidDimensions(width, height));
+    }
+
+    let offset = read_u32(data, NAME_SIZE + 8)? as usize;
+    let indices = read_bytes(data, offset, width as usize * height as usize)?;
+
+    Ok(decode_indexed(width, height, indices, palette, None))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::decode_wal;
+    use crate::texture_formats::{Palette, TextureFormatError};
+
+    #[test]
+    fn test_decode_wal() {
+        let palette =
+            Palette::from_bytes(&(0..=255u8).flat_map(|i| [i, i, i]).collect::<Vec<_>>()).unwrap();
+
+        let mut data = vec![0; 32];
+        data.extend(2u32.to_le_bytes());
+        data.extend(2u32.to_le_bytes());
+        // Mip offsets, followed by the animation name, flags, contents and value
+        data.extend(100u32.to_le_bytes());
+        data.extend([0; 12 + 32 + 12]);
+        data.extend([1, 2, 3, 255]);
+
+        let texture = decode_wal(&data, &palette).expect("failed to decode");
+        assert_eq!((texture.width, texture.height), (2, 2));
+        assert_eq!(&texture.data[12..], &[255, 255, 255, 255]);
+
+        data.truncate(102);
+        assert_eq!(
+            decode_wal(&data, &palette),
+            Err(TextureFormatError::UnexpectedEof)
+        );
+    }
+}
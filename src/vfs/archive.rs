@@ -0,0 +1,317 @@
+use super::VfsError;
+use crate::{
+    binary::{read_bytes, read_name, read_u16, read_u32},
+    game_config::PackageFormat,
+};
+
+const PAK_NAME_SIZE: usize = 56;
+const ID_PAK_ENTRY_SIZE: usize = 64;
+const DK_PAK_ENTRY_SIZE: usize = 72;
+
+const ZIP_EOCD_SIGNATURE: u32 = 0x06054b50;
+const ZIP_EOCD_SIZE: usize = 22;
+const ZIP_CENTRAL_SIGNATURE: u32 = 0x02014b50;
+const ZIP_LOCAL_SIGNATURE: u32 = 0x04034b50;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Compression {
+    None,
+    Deflate,
+    Daikatana,
+}
+
+#[derive(Clone, Debug)]
+struct ArchiveEntry {
+    path: String,
+    offset: usize,
+    size: usize,
+    uncompressed_size: usize,
+    compression: Compression,
+}
+
+/// A read-only package of files, such as a Quake `.pak` or a `.pk3`/`.zip`.
+/// Paths use `/` separators and are matched case-insensitively.
+#[derive(Clone, Debug)]
+pub struct Archive {
+    data: Vec<u8>,
+    entries: Vec<ArchiveEntry>,
+}
+
+impl Archive {
+    pub fn parse(format: PackageFormat, data: Vec<u8>) -> Result<Self, VfsError> {
+        let entries = match format {
+            PackageFormat::IdPak => parse_pak(&data, ID_PAK_ENTRY_SIZE)?,
+            PackageFormat::DkPak => parse_pak(&data, DK_PAK_ENTRY_SIZE)?,
+            PackageFormat::Zip => parse_zip(&data)?,
+        };
+
+        Ok(Self { data, entries })
+    }
+
+    /// Paths of all files in the archive
+    pub fn files(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|entry| entry.path.as_str())
+    }
+
+    pub fn contains(&self, path: &str) -> bool {
+        self.entry(path).is_some()
+    }
+
+    /// Reads and decompresses a file, or returns `None` if the archive doesn't contain it
+    pub fn read(&self, path: &str) -> Option<Result<Vec<u8>, VfsError>> {
+        self.entry(path).map(|entry| self.read_entry(entry))
+    }
+
+    fn entry(&self, path: &str) -> Option<&ArchiveEntry> {
+        let path = normalize_path(path);
+
+        self.entries
+            .iter()
+            .find(|entry| entry.path.eq_ignore_ascii_case(&path))
+    }
+
+    fn read_entry(&self, entry: &ArchiveEntry) -> Result<Vec<u8>, VfsError> {
+        let data = read_bytes(&self.data, entry.offset, entry.size)?;
+
+        let contents = match entry.compression {
+            Compression::None => data.to_vec(),
+            Compression::Deflate => miniz_oxide::inflate::decompress_to_vec(data)
+                .map_err(|_| VfsError::Decompression(entry.path.clone()))?,
+            Compression::Daikatana => decompress_daikatana(data),
+        };
+
+        if contents.len() != entry.uncompressed_size {
+            return Err(VfsError::Decompression(entry.path.clone()));
+        }
+
+        Ok(contents)
+    }
+}
+
+pub(crate) fn normalize_path(path: &str) -> String {
+    path.replace('\\', "/").trim_start_matches("./").to_string()
+}
+
+/// Quake and Daikatana PAKs, which differ only in directory entry size and compression
+fn parse_pak(data: &[u8], entry_size: usize) -> Result<Vec<ArchiveEntry>, VfsError> {
+    if read_bytes(data, 0, 4)? != b"PACK" {
+        return Err(VfsError::InvalidMagic);
+    }
+
+    let dir_offset = read_u32(data, 4)? as usize;
+    let dir_size = read_u32(data, 8)? as usize;
+
+    let mut entries = Vec::with_capacity(dir_size / entry_size);
+
+    for i in 0..(dir_size / entry_size) {
+        let entry = dir_offset + i * entry_size;
+
+        let path = normalize_path(&read_name(data, entry, PAK_NAME_SIZE)?);
+        let offset = read_u32(data, entry + PAK_NAME_SIZE)? as usize;
+        let uncompressed_size = read_u32(data, entry + PAK_NAME_SIZE + 4)? as usize;
+
+        let (size, compression) = if entry_size == DK_PAK_ENTRY_SIZE
+            && read_u32(data, entry + PAK_NAME_SIZE + 12)? != 0
+        {
+            let compressed_size = read_u32(data, entry + PAK_NAME_SIZE + 8)? as usize;
+            (compressed_size, Compression::Daikatana)
+        } else {
+            (uncompressed_size, Compression::None)
+        };
+
+        entries.push(ArchiveEntry {
+            path,
+            offset,
+            size,
+            uncompressed_size,
+            compression,
+        });
+    }
+
+    Ok(entries)
+}
+
+/// Zip files (and `.pk3`), without support for Zip64 or encryption
+fn parse_zip(data: &[u8]) -> Result<Vec<ArchiveEntry>, VfsError> {
+    // The end of central directory record is followed by a variable-length comment
+    let eocd = (0..=data.len().saturating_sub(ZIP_EOCD_SIZE))
+        .rev()
+        .find(|offset| read_u32(data, *offset) == Ok(ZIP_EOCD_SIGNATURE))
+        .ok_or(VfsError::InvalidMagic)?;
+
+    let num_entries = read_u16(data, eocd + 10)? as usize;
+    let mut entry = read_u32(data, eocd + 16)? as usize;
+
+    let mut entries = Vec::with_capacity(num_entries);
+
+    for _ in 0..num_entries {
+        if read_u32(data, entry)? != ZIP_CENTRAL_SIGNATURE {
+            return Err(VfsError::InvalidMagic);
+        }
+
+        let method = read_u16(data, entry + 10)?;
+        let size = read_u32(data, entry + 20)? as usize;
+        let uncompressed_size = read_u32(data, entry + 24)? as usize;
+        let name_len = read_u16(data, entry + 28)? as usize;
+        let extra_len = read_u16(data, entry + 30)? as usize;
+        let comment_len = read_u16(data, entry + 32)? as usize;
+        let local_header = read_u32(data, entry + 42)? as usize;
+        let path = String::from_utf8_lossy(read_bytes(data, entry + 46, name_len)?).into_owned();
+
+        entry += 46 + name_len + extra_len + comment_len;
+
+        // Directories are implied by file paths
+        if path.ends_with('/') {
+            continue;
+        }
+
+        let compression = match method {
+            0 => Compression::None,
+            8 => Compression::Deflate,
+            _ => return Err(VfsError::UnsupportedCompression(path)),
+        };
+
+        // The local header's extra field can differ from the central directory's
+        if read_u32(data, local_header)? != ZIP_LOCAL_SIGNATURE {
+            return Err(VfsError::InvalidMagic);
+        }
+
+        let local_name_len = read_u16(data, local_header + 26)? as usize;
+        let local_extra_len = read_u16(data, local_header + 28)? as usize;
+
+        entries.push(ArchiveEntry {
+            path: normalize_path(&path),
+            offset: local_header + 30 + local_name_len + local_extra_len,
+            size,
+            uncompressed_size,
+            compression,
+        });
+    }
+
+    Ok(entries)
+}
+
+/// Daikatana's PAK compression, a mix of run-length encoding and back-references
+/// (https://github.com/TrenchBroom/TrenchBroom/blob/master/common/src/IO/DkPakFileSystem.cpp)
+fn decompress_daikatana(data: &[u8]) -> Vec<u8> {
+    let mut output = Vec::new();
+    let mut i = 0;
+
+    while i < data.len() {
+        let x = data[i] as usize;
+        i += 1;
+
+        match x {
+            // Uncompressed bytes
+            0..=63 => {
+                let end = (i + x + 1).min(data.len());
+                output.extend_from_slice(&data[i..end]);
+                i = end;
+            }
+            // Zeroes
+            64..=127 => output.resize(output.len() + x - 62, 0),
+            // Repeated byte
+            128..=191 => {
+                if let Some(byte) = data.get(i) {
+                    output.resize(output.len() + x - 126, *byte);
+                }
+                i += 1;
+            }
+            // Copy of previous output
+            192..=254 => {
+                let back = data.get(i).map_or(0, |b| *b as usize) + 2;
+                i += 1;
+
+                let start = output.len().saturating_sub(back);
+                for j in 0..(x - 190) {
+                    let byte = output.get(start + j).copied().unwrap_or_default();
+                    output.push(byte);
+                }
+            }
+            _ => break,
+        }
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decompress_daikatana, Archive};
+    use crate::{
+        game_config::PackageFormat,
+        vfs::test_support::{build_pak, build_zip},
+        vfs::VfsError,
+    };
+
+    #[test]
+    fn test_pak() {
+        let data = build_pak(&[
+            ("gfx/palette.lmp", b"palette"),
+            ("textures/wall.png", b"wall"),
+        ]);
+
+        let archive = Archive::parse(PackageFormat::IdPak, data).expect("failed to parse pak");
+
+        assert_eq!(
+            archive.files().collect::<Vec<_>>(),
+            vec!["gfx/palette.lmp", "textures/wall.png"]
+        );
+        assert!(archive.contains("TEXTURES/Wall.png"));
+        assert_eq!(archive.read("textures/wall.png").unwrap().unwrap(), b"wall");
+        assert!(archive.read("textures/floor.png").is_none());
+
+        assert!(matches!(
+            Archive::parse(PackageFormat::IdPak, b"WAD2".to_vec()),
+            Err(VfsError::InvalidMagic)
+        ));
+    }
+
+    #[test]
+    fn test_zip() {
+        let data = build_zip(&[("textures/", b""), ("textures/wall.png", b"wall")]);
+
+        let archive = Archive::parse(PackageFormat::Zip, data).expect("failed to parse zip");
+
+        assert_eq!(
+            archive.files().collect::<Vec<_>>(),
+            vec!["textures/wall.png"]
+        );
+        assert_eq!(archive.read("textures/wall.png").unwrap().unwrap(), b"wall");
+    }
+
+    #[test]
+    fn test_deflate() {
+        let contents = b"wall wall wall wall wall";
+        let compressed = miniz_oxide::deflate::compress_to_vec(contents, 6);
+
+        let mut data = build_zip(&[("wall.txt", &compressed)]);
+
+        // Patch the method and uncompressed size in both headers
+        let central = data.len() - 22 - 46 - "wall.txt".len();
+        for header in [0, central] {
+            let method_offset = if header == 0 { 8 } else { header + 10 };
+            let size_offset = if header == 0 { 22 } else { header + 24 };
+
+            data[method_offset] = 8;
+            data[size_offset..size_offset + 4]
+                .copy_from_slice(&(contents.len() as u32).to_le_bytes());
+        }
+
+        let archive = Archive::parse(PackageFormat::Zip, data).expect("failed to parse zip");
+        assert_eq!(archive.read("wall.txt").unwrap().unwrap(), contents);
+    }
+
+    #[test]
+    fn test_decompress_daikatana() {
+        let compressed = [
+            2, b'a', b'b', b'c', // Uncompressed "abc"
+            64,   // 2 zeroes
+            130, b'x', // 4 "x"
+            192, 6, // 2 bytes starting 8 bytes back
+            255,
+        ];
+
+        assert_eq!(decompress_daikatana(&compressed), b"abc\0\0xxxxbc".to_vec());
+    }
+}
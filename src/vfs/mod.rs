@@ -0,0 +1,216 @@
+//! A read-only virtual filesystem over game packages (PAK, PK3 and ZIP),
+//! as described by a game configuration's `FilesystemSettings`.
+
+use crate::{
+    binary::UnexpectedEof,
+    game_config::{FilesystemSettings, PackageFormat},
+};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+mod archive;
+pub use archive::*;
+
+#[cfg(any(test, feature = "test-support"))]
+pub mod test_support;
+
+#[derive(Error, Debug)]
+pub enum VfsError {
+    #[error("unexpected end of data")]
+    UnexpectedEof,
+    #[error("unrecognized package format")]
+    InvalidMagic,
+    #[error("unsupported compression method for {0}")]
+    UnsupportedCompression(String),
+    #[error("failed to decompress {0}")]
+    Decompression(String),
+}
+
+impl From<UnexpectedEof> for VfsError {
+    fn from(_: UnexpectedEof) -> Self {
+        VfsError::UnexpectedEof
+    }
+}
+
+/// A set of mounted archives. Archives mounted later take priority, like `pak1.pak` over `pak0.pak` in Quake.
+#[derive(Clone, Debug, Default)]
+pub struct Vfs {
+    archives: Vec<Archive>,
+}
+
+impl Vfs {
+    pub fn mount(&mut self, archive: Archive) {
+        self.archives.push(archive);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.archives.is_empty()
+    }
+
+    /// Selects the packages to mount from the files in the search path, in mounting order
+    pub fn package_paths(
+        settings: &FilesystemSettings,
+        files: impl Iterator<Item = PathBuf>,
+    ) -> Vec<(PathBuf, PackageFormat)> {
+        let mut packages = files
+            .filter(|path| {
+                path.extension()
+                    .and_then(|ext| ext.to_str())
+                    .is_some_and(|ext| settings.package_format.matches_extension(ext))
+            })
+            .collect::<Vec<_>>();
+
+        packages.sort_by_cached_key(|path| natural_key(path));
+
+        let format = *settings.package_format.format();
+        packages.into_iter().map(|path| (path, format)).collect()
+    }
+
+    pub fn contains(&self, path: &Path) -> bool {
+        let path = path.to_string_lossy();
+        self.archives.iter().any(|archive| archive.contains(&path))
+    }
+
+    pub fn read_file(&self, path: &Path) -> Option<Result<Vec<u8>, VfsError>> {
+        let path = path.to_string_lossy();
+        self.archives
+            .iter()
+            .rev()
+            .find_map(|archive| archive.read(&path))
+    }
+
+    /// Lists the files and directories directly inside `path`
+    pub fn read_directory(&self, path: &Path) -> Vec<PathBuf> {
+        let mut prefix = normalize_path(&path.to_string_lossy())
+            .trim_end_matches('/')
+            .to_ascii_lowercase();
+
+        if !prefix.is_empty() {
+            prefix.push('/');
+        }
+
+        let mut children = Vec::new();
+
+        for file in self.archives.iter().flat_map(|archive| archive.files()) {
+            if !file.to_ascii_lowercase().starts_with(&prefix) {
+                continue;
+            }
+
+            let child = file[prefix.len()..].split('/').next().unwrap_or_default();
+            let child = path.join(child);
+
+            if !children.contains(&child) {
+                children.push(child);
+            }
+        }
+
+        children
+    }
+}
+
+/// Splits a path into text followed by a number, so `pak2.pak` sorts before `pak10.pak`
+fn natural_key(path: &Path) -> Vec<(String, Option<u64>)> {
+    let path = path.to_string_lossy();
+    let mut key = Vec::new();
+    let mut text = String::new();
+    let mut chars = path.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if !c.is_ascii_digit() {
+            text.push(c);
+            continue;
+        }
+
+        let mut digits = c.to_string();
+        while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+            digits.push(digit);
+        }
+
+        key.push((
+            std::mem::take(&mut text),
+            Some(digits.parse().unwrap_or(u64::MAX)),
+        ));
+    }
+
+    if !text.is_empty() {
+        key.push((text, None));
+    }
+
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Archive, Vfs};
+    use crate::{
+        game_config::{FilesystemSettings, PackageFormat, PackageFormatSettings},
+        vfs::test_support::{build_pak, build_zip},
+    };
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn test_vfs() {
+        let mut vfs = Vfs::default();
+
+        let pak0 = build_pak(&[
+            ("textures/wall.png", b"old wall"),
+            ("textures/floor.png", b"floor"),
+        ]);
+        let pak1 = build_zip(&[
+            ("textures/wall.png", b"new wall"),
+            ("textures/base/trim.png", b"trim"),
+        ]);
+
+        vfs.mount(Archive::parse(PackageFormat::IdPak, pak0).unwrap());
+        vfs.mount(Archive::parse(PackageFormat::Zip, pak1).unwrap());
+
+        assert!(vfs.contains(Path::new("textures/floor.png")));
+        assert_eq!(
+            vfs.read_file(Path::new("textures/wall.png"))
+                .unwrap()
+                .unwrap(),
+            b"new wall"
+        );
+        assert!(vfs.read_file(Path::new("textures/ceiling.png")).is_none());
+
+        assert_eq!(
+            vfs.read_directory(Path::new("textures")),
+            vec![
+                PathBuf::from("textures/wall.png"),
+                PathBuf::from("textures/floor.png"),
+                PathBuf::from("textures/base"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_package_paths() {
+        let settings = FilesystemSettings {
+            search_path: "id1".to_string(),
+            package_format: PackageFormatSettings::Extensions {
+                extensions: vec!["pak".to_string(), "pk3".to_string()],
+                format: PackageFormat::Zip,
+            },
+        };
+
+        let files = [
+            "id1/pak10.pak",
+            "id1/pak1.PAK",
+            "id1/config.cfg",
+            "id1/pak2.pak",
+            "id1/pak0.pk3",
+        ]
+        .map(PathBuf::from);
+
+        // Numbers are compared by value, so pak10 overrides pak2
+        assert_eq!(
+            Vfs::package_paths(&settings, files.into_iter()),
+            vec![
+                (PathBuf::from("id1/pak0.pk3"), PackageFormat::Zip),
+                (PathBuf::from("id1/pak1.PAK"), PackageFormat::Zip),
+                (PathBuf::from("id1/pak2.pak"), PackageFormat::Zip),
+                (PathBuf::from("id1/pak10.pak"), PackageFormat::Zip),
+            ]
+        );
+    }
+}
@@ -0,0 +1,70 @@
+//! Builders for package fixtures, shared with the editor's tests through the `test-support` feature
+
+/// An id PAK containing `files`
+pub fn build_pak(files: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut data = b"PACK".to_vec();
+
+    let dir_offset = 12 + files.iter().map(|f| f.1.len()).sum::<usize>();
+    data.extend((dir_offset as u32).to_le_bytes());
+    data.extend(((files.len() * 64) as u32).to_le_bytes());
+
+    for (_, contents) in files {
+        data.extend(*contents);
+    }
+
+    let mut offset = 12;
+    for (name, contents) in files {
+        let mut name_bytes = name.as_bytes().to_vec();
+        name_bytes.resize(56, 0);
+        data.extend(name_bytes);
+        data.extend((offset as u32).to_le_bytes());
+        data.extend((contents.len() as u32).to_le_bytes());
+
+        offset += contents.len();
+    }
+
+    data
+}
+
+/// A zip containing `files`, stored without compression
+pub fn build_zip(files: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut data = Vec::new();
+    let mut central = Vec::new();
+
+    for (name, contents) in files {
+        let local_offset = data.len() as u32;
+        let sizes = (contents.len() as u32).to_le_bytes();
+
+        data.extend(0x04034b50u32.to_le_bytes());
+        data.extend([0; 14]);
+        data.extend(sizes);
+        data.extend(sizes);
+        data.extend((name.len() as u16).to_le_bytes());
+        data.extend(0u16.to_le_bytes());
+        data.extend(name.as_bytes());
+        data.extend(*contents);
+
+        central.extend(0x02014b50u32.to_le_bytes());
+        central.extend([0; 16]);
+        central.extend(sizes);
+        central.extend(sizes);
+        central.extend((name.len() as u16).to_le_bytes());
+        central.extend([0; 12]);
+        central.extend(local_offset.to_le_bytes());
+        central.extend(name.as_bytes());
+    }
+
+    let central_offset = data.len() as u32;
+    let central_size = central.len() as u32;
+    data.extend(central);
+
+    data.extend(0x06054b50u32.to_le_bytes());
+    data.extend([0; 4]);
+    data.extend((files.len() as u16).to_le_bytes());
+    data.extend((files.len() as u16).to_le_bytes());
+    data.extend(central_size.to_le_bytes());
+    data.extend(central_offset.to_le_bytes());
+    data.extend(0u16.to_le_bytes());
+
+    data
+}